@@ -0,0 +1,423 @@
+use crate::events::Event;
+#[cfg(feature = "serde")]
+use crate::schema::SchemaVersion;
+use crate::sysfs::read_string;
+use crate::{
+    counter_delta, find_cpu, package_values, CorePower, CpuInfo, EnergySnapshot, Error,
+    PackageAggregation,
+};
+use std::fs::{read_to_string, write};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Gaps between the monotonic and boot clock longer than this are reported as a suspend
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// First line of a saved energy state, including the format version
+const STATE_HEADER: &str = "ryzen-reader-energy 1";
+
+/// Boot times calculated from the uptime further apart than this are considered different boots
+const BOOT_TIME_TOLERANCE: f64 = 5.0;
+
+/// Energy accumulated since the [`CpuInfo`] was created
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccumulatedEnergy {
+    cores: Vec<CorePower>,
+    package_aggregation: PackageAggregation,
+    elapsed: Duration,
+    energy_unit: f64,
+    time_unit: f64,
+    #[cfg(feature = "serde")]
+    #[serde(default)]
+    schema_version: SchemaVersion,
+}
+
+/// Average power over a long window together with the uncertainty of the estimate
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AverageEstimate {
+    /// The average power draw in watt
+    pub power: f64,
+    /// The maximum error of the average in watt
+    pub uncertainty: f64,
+}
+
+impl AccumulatedEnergy {
+    /// Get an iterator for all cpu cores in the system and their accumulated energy in joule
+    pub fn cores(&self) -> impl Iterator<Item = f64> + '_ {
+        self.cores.iter().filter_map(|core| core.core_power)
+    }
+
+    /// Get an iterator for all cpu packages in the system and their accumulated energy in joule
+    pub fn packages(&self) -> impl Iterator<Item = f64> + '_ {
+        package_values(&self.cores, self.package_aggregation).map(|(_, energy)| energy)
+    }
+
+    /// The time over which the energy was accumulated
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Get an iterator for all cpu cores in the system and their average power draw over the elapsed time in watt
+    ///
+    /// Since the energy is accumulated by every read this gives a proper long term average, even when sampling sparsely.
+    pub fn average_cores(&self) -> impl Iterator<Item = f64> + '_ {
+        let seconds = self.elapsed.as_secs_f64();
+        self.cores().map(move |energy| energy / seconds)
+    }
+
+    /// Get an iterator for all cpu packages in the system and their average power draw over the elapsed time in watt
+    pub fn average_packages(&self) -> impl Iterator<Item = f64> + '_ {
+        let seconds = self.elapsed.as_secs_f64();
+        self.packages().map(move |energy| energy / seconds)
+    }
+
+    /// The RAPL time unit in seconds, the interval at which the energy counters are updated
+    pub fn time_unit(&self) -> f64 {
+        self.time_unit
+    }
+
+    /// Get the average power of all cpu cores in the system together with the uncertainty of the average
+    ///
+    /// The energy counters are only updated once every [`time_unit`](AccumulatedEnergy::time_unit),
+    /// so both ends of the window can be one time unit stale and are rounded to the energy unit.
+    /// Returns nothing when the window is shorter than a time unit, the counters might not have updated yet.
+    pub fn average_core_estimates(&self) -> impl Iterator<Item = AverageEstimate> + '_ {
+        let cores = self.cores();
+        self.estimates(cores)
+    }
+
+    /// Get the average power of all cpu packages in the system together with the uncertainty of the average
+    pub fn average_package_estimates(&self) -> impl Iterator<Item = AverageEstimate> + '_ {
+        let packages = self.packages();
+        self.estimates(packages)
+    }
+
+    fn estimates<'a>(
+        &self,
+        energy: impl Iterator<Item = f64> + 'a,
+    ) -> impl Iterator<Item = AverageEstimate> + 'a {
+        let seconds = self.elapsed.as_secs_f64();
+        let (energy_unit, time_unit) = (self.energy_unit, self.time_unit);
+        energy
+            .filter(move |_| seconds >= time_unit)
+            .map(move |energy| {
+                let power = energy / seconds;
+                AverageEstimate {
+                    power,
+                    uncertainty: 2.0 * (energy_unit + power * time_unit) / seconds,
+                }
+            })
+    }
+}
+
+/// Running energy totals, updated with every snapshot taken
+#[derive(Debug)]
+pub(crate) struct Accumulator {
+    start: Instant,
+    last: EnergySnapshot,
+    energy: Vec<CorePower>,
+}
+
+impl Accumulator {
+    pub fn new(snapshot: EnergySnapshot) -> Self {
+        Accumulator {
+            start: snapshot.time,
+            energy: snapshot
+                .counters
+                .iter()
+                .map(|counters| CorePower {
+                    core_power: counters.core_energy.map(|_| 0.0),
+                    package_power: counters.package_energy.map(|_| 0.0),
+                    cpu: counters.cpu,
+                    package: counters.package,
+                    physical: counters.physical,
+                    ccd: counters.ccd,
+                    frequency: None,
+                    voltage: None,
+                    pstate: None,
+                    interval: Duration::ZERO,
+                })
+                .collect(),
+            last: snapshot,
+        }
+    }
+}
+
+impl CpuInfo {
+    pub(crate) fn accumulate(&self, snapshot: &EnergySnapshot) {
+        let mut accumulator = self.accumulator.lock().unwrap();
+        let accumulator = match accumulator.as_mut() {
+            Some(accumulator) => accumulator,
+            None => {
+                *accumulator = Some(Accumulator::new(snapshot.clone()));
+                return;
+            }
+        };
+        // snapshots taken concurrently can arrive out of order
+        if snapshot.time < accumulator.last.time {
+            return;
+        }
+
+        let (energy, wraparounds) = accumulator.last.energy_until(snapshot);
+        if wraparounds > 0 {
+            self.events.push(Event::Wraparound { count: wraparounds });
+        }
+        if let (Some((last_time, last_boot)), Some((time, boot))) =
+            (accumulator.last.suspend_clock, snapshot.suspend_clock)
+        {
+            // the monotonic clock stops while suspended, the boot time keeps counting
+            let suspended = boot
+                .saturating_sub(last_boot)
+                .saturating_sub(time.saturating_duration_since(last_time));
+            if suspended > SUSPEND_THRESHOLD {
+                self.events.push(Event::SuspendGap {
+                    duration: suspended,
+                });
+            }
+        }
+        if accumulator.energy.len() != energy.len() {
+            // cores were added by a rescan, their totals start now
+            let fresh = Accumulator::new(snapshot.clone()).energy;
+            accumulator.energy = fresh
+                .into_iter()
+                .enumerate()
+                .map(|(index, fresh)| {
+                    find_cpu(&accumulator.energy, index, fresh.cpu, |total| total.cpu)
+                        .cloned()
+                        .unwrap_or(fresh)
+                })
+                .collect();
+        }
+        for (total, energy) in accumulator.energy.iter_mut().zip(energy) {
+            if energy.interval.is_zero() {
+                // an added core without a previous reading
+                continue;
+            }
+            total.core_power = total.core_power.zip(energy.core_power).map(|(a, b)| a + b);
+            total.package_power = total
+                .package_power
+                .zip(energy.package_power)
+                .map(|(a, b)| a + b);
+        }
+        accumulator.last = snapshot.clone();
+    }
+
+    /// Get the energy used by every core and package since this `CpuInfo` was created
+    ///
+    /// This allows bracketing a region of code with two cheap calls and subtracting the results.
+    /// The totals are updated by every read, as long as at least one read or call to this method happens
+    /// within every [`max_interval`](CpuInfo::max_interval) the totals are not affected by counter wraparound.
+    pub fn energy_since_init(&self) -> Result<AccumulatedEnergy, Error> {
+        let snapshot = self.snapshot()?;
+        let accumulator = self.accumulator.lock().unwrap();
+        let accumulator = accumulator
+            .as_ref()
+            .expect("accumulator is initialized on creation");
+
+        Ok(AccumulatedEnergy {
+            cores: accumulator.energy.clone(),
+            package_aggregation: self.package_aggregation,
+            elapsed: snapshot.time.duration_since(accumulator.start),
+            energy_unit: self.units.energy_unit,
+            time_unit: self.units.time_unit,
+            #[cfg(feature = "serde")]
+            schema_version: SchemaVersion,
+        })
+    }
+}
+
+/// Seconds since boot, from `/proc/uptime`
+fn uptime() -> io::Result<f64> {
+    read_string("/proc/uptime")?
+        .split(' ')
+        .next()
+        .and_then(|uptime| uptime.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid uptime"))
+}
+
+/// The wall clock time of the boot in seconds since the epoch
+fn boot_time(uptime: f64) -> f64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    now.as_secs_f64() - uptime
+}
+
+fn format_option<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".into(), |value| value.to_string())
+}
+
+fn parse_option<T: std::str::FromStr>(value: &str) -> Option<Option<T>> {
+    match value {
+        "-" => Some(None),
+        value => value.parse().ok().map(Some),
+    }
+}
+
+/// The saved raw counters and totals of a single core
+struct SavedCore {
+    cpu: u32,
+    core_energy: Option<u64>,
+    package_energy: Option<u64>,
+    core_total: Option<f64>,
+    package_total: Option<f64>,
+}
+
+/// The energy state saved by [`CpuInfo::save_energy`]
+struct SavedState {
+    boot_time: f64,
+    uptime: f64,
+    energy_unit: f64,
+    elapsed: Duration,
+    cores: Vec<SavedCore>,
+}
+
+impl SavedState {
+    fn parse(state: &str) -> Option<Self> {
+        let mut lines = state.lines();
+        if lines.next()? != STATE_HEADER {
+            return None;
+        }
+        let mut fields = lines.next()?.split(' ');
+        let boot_time = fields.next()?.parse().ok()?;
+        let uptime = fields.next()?.parse().ok()?;
+        let energy_unit = fields.next()?.parse().ok()?;
+        let elapsed = Duration::try_from_secs_f64(fields.next()?.parse().ok()?).ok()?;
+
+        let cores = lines
+            .map(|line| {
+                let mut fields = line.split(' ');
+                Some(SavedCore {
+                    cpu: fields.next()?.parse().ok()?,
+                    core_energy: parse_option(fields.next()?)?,
+                    package_energy: parse_option(fields.next()?)?,
+                    core_total: parse_option(fields.next()?)?,
+                    package_total: parse_option(fields.next()?)?,
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(SavedState {
+            boot_time,
+            uptime,
+            energy_unit,
+            elapsed,
+            cores,
+        })
+    }
+}
+
+impl CpuInfo {
+    /// Save the accumulated energy and the current raw counters to a file
+    ///
+    /// A later process can continue the totals from the saved state with [`resume_energy`](CpuInfo::resume_energy),
+    /// allowing a daemon to keep its totals over a restart.
+    pub fn save_energy<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        self.snapshot()?;
+        let uptime = uptime().map_err(Error::IO)?;
+        let accumulator = self.accumulator.lock().unwrap();
+        let accumulator = accumulator
+            .as_ref()
+            .expect("accumulator is initialized on creation");
+
+        let mut state = format!(
+            "{}\n{} {} {} {}\n",
+            STATE_HEADER,
+            boot_time(uptime),
+            uptime,
+            accumulator.last.energy_unit,
+            accumulator
+                .last
+                .time
+                .duration_since(accumulator.start)
+                .as_secs_f64()
+        );
+        for (counters, total) in accumulator.last.counters.iter().zip(&accumulator.energy) {
+            state.push_str(&format!(
+                "{} {} {} {} {}\n",
+                counters.cpu,
+                format_option(counters.core_energy),
+                format_option(counters.package_energy),
+                format_option(total.core_power),
+                format_option(total.package_power)
+            ));
+        }
+
+        write(path, state).map_err(Error::IO)
+    }
+
+    /// Continue the accumulated energy from a state saved with [`save_energy`](CpuInfo::save_energy)
+    ///
+    /// The energy used while no process was running is included in the totals.
+    /// This should be called right after creating the `CpuInfo`.
+    ///
+    /// Returns `false` and leaves the totals untouched if there is no saved state, the system rebooted since it was saved,
+    /// it was saved for a different set of cpus or more than [`max_interval`](CpuInfo::max_interval) ago,
+    /// in which case the energy used in between can't be determined reliably.
+    pub fn resume_energy<P: AsRef<Path>>(&self, path: P) -> Result<bool, Error> {
+        let state = match read_to_string(path) {
+            Ok(state) => state,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(Error::IO(e)),
+        };
+        let saved = match SavedState::parse(&state) {
+            Some(saved) => saved,
+            None => return Ok(false),
+        };
+
+        let snapshot = self.snapshot()?;
+        let uptime = uptime().map_err(Error::IO)?;
+        let rebooted = uptime < saved.uptime
+            || (boot_time(uptime) - saved.boot_time).abs() > BOOT_TIME_TOLERANCE;
+        let gap = Duration::from_secs_f64((uptime - saved.uptime).max(0.0));
+        if rebooted
+            || gap > self.max_interval()
+            || saved.energy_unit != snapshot.energy_unit
+            || saved.cores.len() != snapshot.counters.len()
+        {
+            return Ok(false);
+        }
+
+        let mut accumulator = self.accumulator.lock().unwrap();
+        let accumulator = accumulator
+            .as_mut()
+            .expect("accumulator is initialized on creation");
+        let last = &accumulator.last;
+        if saved
+            .cores
+            .iter()
+            .zip(&last.counters)
+            .any(|(saved, counters)| saved.cpu != counters.cpu)
+        {
+            return Ok(false);
+        }
+        let start = match last.time.checked_sub(saved.elapsed + gap) {
+            Some(start) => start,
+            None => return Ok(false),
+        };
+
+        // the totals continue from the saved counters, which covers the gap and everything accumulated since
+        let energy = |saved: Option<u64>, current: Option<u64>, total: Option<f64>| {
+            let (delta, _) = counter_delta(saved?, current?);
+            Some(total? + delta as f64 * last.energy_unit)
+        };
+        for (saved, (counters, total)) in saved
+            .cores
+            .into_iter()
+            .zip(last.counters.iter().zip(&mut accumulator.energy))
+        {
+            total.core_power = energy(saved.core_energy, counters.core_energy, saved.core_total);
+            total.package_power = energy(
+                saved.package_energy,
+                counters.package_energy,
+                saved.package_total,
+            );
+        }
+        accumulator.start = start;
+
+        Ok(true)
+    }
+}
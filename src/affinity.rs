@@ -0,0 +1,89 @@
+use std::io;
+#[cfg(target_os = "linux")]
+use std::mem::{size_of, size_of_val};
+
+#[cfg(target_os = "linux")]
+const MASK_WORD_BITS: usize = libc::c_ulong::BITS as usize;
+
+/// Restores the original cpu affinity of the thread when dropped
+pub struct AffinityGuard {
+    #[cfg(target_os = "linux")]
+    original: Vec<libc::c_ulong>,
+}
+
+/// Get the affinity mask of the calling thread
+///
+/// `cpu_set_t` only fits 1024 cpus, the mask is grown until it fits all cpus the kernel supports.
+#[cfg(target_os = "linux")]
+fn get_affinity() -> io::Result<Vec<libc::c_ulong>> {
+    let mut words = size_of::<libc::cpu_set_t>() / size_of::<libc::c_ulong>();
+    loop {
+        let mut mask = vec![0; words];
+        let result = unsafe {
+            libc::sched_getaffinity(
+                0,
+                words * size_of::<libc::c_ulong>(),
+                mask.as_mut_ptr() as *mut libc::cpu_set_t,
+            )
+        };
+        if result == 0 {
+            return Ok(mask);
+        }
+        let error = io::Error::last_os_error();
+        // the mask is smaller than the kernel's cpu mask
+        if error.raw_os_error() == Some(libc::EINVAL) && words < 1 << 16 {
+            words *= 2;
+        } else {
+            return Err(error);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(mask: &[libc::c_ulong]) -> io::Result<()> {
+    let result = unsafe {
+        libc::sched_setaffinity(
+            0,
+            size_of_val(mask),
+            mask.as_ptr() as *const libc::cpu_set_t,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Pin the calling thread to a set of cpus until the returned guard is dropped
+#[cfg(target_os = "linux")]
+pub fn pin_to<I: IntoIterator<Item = u32>>(cpus: I) -> io::Result<AffinityGuard> {
+    let original = get_affinity()?;
+
+    let cpus: Vec<usize> = cpus.into_iter().map(|cpu| cpu as usize).collect();
+    let words = cpus
+        .iter()
+        .map(|cpu| cpu / MASK_WORD_BITS + 1)
+        .max()
+        .unwrap_or(0)
+        .max(original.len());
+    let mut mask = vec![0; words];
+    for cpu in cpus {
+        mask[cpu / MASK_WORD_BITS] |= 1 << (cpu % MASK_WORD_BITS);
+    }
+    set_affinity(&mask)?;
+
+    Ok(AffinityGuard { original })
+}
+
+/// Pinning threads is only supported on linux
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to<I: IntoIterator<Item = u32>>(_cpus: I) -> io::Result<AffinityGuard> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+impl Drop for AffinityGuard {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        let _ = set_affinity(&self.original);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{CpuInfo, CpuPower, EnergySnapshot, Error};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::spawn_blocking;
+use tokio::time::sleep;
+
+impl CpuInfo {
+    /// Read the cpu power levels without blocking the async runtime
+    ///
+    /// The msr reads are done on the blocking thread pool and the time between the two snapshots
+    /// is waited for with [`tokio::time::sleep`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// # use std::sync::Arc;
+    /// #
+    /// # async fn example() -> Result<(), Error> {
+    ///     let cpu = Arc::new(CpuInfo::new()?);
+    ///     let power = cpu.read_async().await?;
+    ///     println!("{:.2}W", power.packages().sum::<f64>());
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub async fn read_async(self: &Arc<Self>) -> Result<CpuPower, Error> {
+        self.read_with_interval_async(Duration::from_millis(10))
+            .await
+    }
+
+    /// Read the cpu power levels averaged over `interval` without blocking the async runtime
+    pub async fn read_with_interval_async(
+        self: &Arc<Self>,
+        interval: Duration,
+    ) -> Result<CpuPower, Error> {
+        let max = self.max_interval();
+        if interval > max {
+            return Err(Error::IntervalTooLong { interval, max });
+        }
+
+        let start = self.snapshot_blocking().await?;
+        sleep(interval).await;
+        let end = self.snapshot_blocking().await?;
+
+        CpuPower::between(&start, &end)
+    }
+
+    pub(crate) async fn snapshot_blocking(self: &Arc<Self>) -> Result<EnergySnapshot, Error> {
+        let cpu = self.clone();
+        spawn_blocking(move || cpu.snapshot())
+            .await
+            .expect("snapshot task panicked")
+    }
+}
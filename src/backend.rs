@@ -0,0 +1,224 @@
+//! Pluggable sources for the energy counters
+//!
+//! By default the counters are read from the msrs or from sysfs, a [`PowerBackend`] set with
+//! [`CpuInfoBuilder::backend`](crate::CpuInfoBuilder::backend) replaces those.
+//! [`MockBackend`] allows testing code that handles power readings without root or AMD hardware.
+
+use crate::clock::{Clock, SystemClock};
+use crate::Error;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// A source for the energy counters of a set of cpus
+///
+/// Values are returned in the format of the AMD msrs: the power unit register encodes the energy unit
+/// in bits 8-12 as `0.5^n` joule, the energy counters are 32 bit counters in that unit.
+/// Returning an `ENXIO` or `EIO` error from one of the energy methods marks that energy domain as unavailable
+/// for the cpu.
+pub trait PowerBackend: Debug + Send + Sync {
+    /// The logical cpus provided by the backend as `(cpu, package)` pairs
+    fn cpus(&self) -> Result<Vec<(u32, u32)>, Error>;
+
+    /// Read the power unit register of a cpu
+    fn read_units(&self, cpu: u32) -> io::Result<u64>;
+
+    /// Read the core energy counter of a cpu
+    fn read_core_energy(&self, cpu: u32) -> io::Result<u64>;
+
+    /// Read the package energy counter through a cpu
+    fn read_package_energy(&self, cpu: u32) -> io::Result<u64>;
+}
+
+/// The energy unit of the mock counters, 2^-16 joule
+const MOCK_ENERGY_UNIT_BITS: u32 = 16;
+
+#[derive(Debug, Clone, Copy)]
+struct MockCore {
+    package: u32,
+    /// Core and package power in watt, `None` for an unavailable domain
+    core_power: Option<f64>,
+    package_power: Option<f64>,
+    /// Energy in joule up to `since`
+    core_energy: f64,
+    package_energy: f64,
+    since: Instant,
+}
+
+impl MockCore {
+    fn advance(&mut self, now: Instant) {
+        let seconds = now.saturating_duration_since(self.since).as_secs_f64();
+        self.core_energy += self.core_power.unwrap_or_default() * seconds;
+        self.package_energy += self.package_power.unwrap_or_default() * seconds;
+        self.since = now;
+    }
+}
+
+/// A backend with simulated cpus that draw a configurable constant power
+///
+/// # Example
+///
+/// ```rust
+/// # use ryzen_reader::{CpuInfo, Error};
+/// # use ryzen_reader::backend::MockBackend;
+/// # use std::time::Duration;
+/// #
+/// # fn main() -> Result<(), Error> {
+///     let backend = MockBackend::new(1, 4);
+///     backend.set_power(0, 5.0, 40.0);
+///     let cpu = CpuInfo::builder().backend(backend).build()?;
+///     let power = cpu.read_with_interval(Duration::from_millis(50))?;
+///     assert!((power.packages().next().unwrap() - 40.0).abs() < 1.0);
+/// #     Ok(())
+/// # }
+///```
+///
+/// Sharing a [`ManualClock`](crate::clock::ManualClock) between the backend and the [`CpuInfo`](crate::CpuInfo)
+/// gives exact readings without waiting:
+///
+/// ```rust
+/// # use ryzen_reader::{CpuInfo, CpuPower, Error};
+/// # use ryzen_reader::backend::MockBackend;
+/// # use ryzen_reader::clock::ManualClock;
+/// # use std::sync::Arc;
+/// # use std::time::Duration;
+/// #
+/// # fn main() -> Result<(), Error> {
+///     let clock = Arc::new(ManualClock::new());
+///     let backend = MockBackend::with_clock(1, 4, clock.clone());
+///     backend.set_power(0, 5.0, 40.0);
+///     let cpu = CpuInfo::builder().backend(backend).clock(clock.clone()).build()?;
+///     let start = cpu.snapshot()?;
+///     clock.advance(Duration::from_secs(2));
+///     let power = CpuPower::between(&start, &cpu.snapshot()?)?;
+///     assert!((power.packages().next().unwrap() - 40.0).abs() < 0.001);
+/// #     Ok(())
+/// # }
+///```
+#[derive(Debug)]
+pub struct MockBackend {
+    cores: Mutex<BTreeMap<u32, MockCore>>,
+    clock: Box<dyn Clock>,
+}
+
+impl MockBackend {
+    /// Create a backend with `packages` packages of `cpus_per_package` cpus each, all drawing no power
+    pub fn new(packages: u32, cpus_per_package: u32) -> Self {
+        Self::with_clock(packages, cpus_per_package, SystemClock)
+    }
+
+    /// Create a backend that uses `clock` to determine how much energy was used
+    pub fn with_clock<C: Clock + 'static>(packages: u32, cpus_per_package: u32, clock: C) -> Self {
+        let layout: Vec<_> = (0..packages)
+            .flat_map(|package| {
+                (0..cpus_per_package)
+                    .map(move |index| (package * cpus_per_package + index, package))
+            })
+            .collect();
+        Self::from_layout(&layout, clock)
+    }
+
+    /// Create a backend with the given `(cpu, package)` pairs, like systems where the kernel interleaves the packages
+    pub fn from_layout<C: Clock + 'static>(layout: &[(u32, u32)], clock: C) -> Self {
+        let now = clock.now();
+        let cores = layout
+            .iter()
+            .map(|&(cpu, package)| {
+                let core = MockCore {
+                    package,
+                    core_power: Some(0.0),
+                    package_power: Some(0.0),
+                    core_energy: 0.0,
+                    package_energy: 0.0,
+                    since: now,
+                };
+                (cpu, core)
+            })
+            .collect();
+        MockBackend {
+            cores: Mutex::new(cores),
+            clock: Box::new(clock),
+        }
+    }
+
+    /// Set the core power of `cpu` and the power of its package in watt
+    pub fn set_power(&self, cpu: u32, core_power: f64, package_power: f64) {
+        let now = self.clock.now();
+        let mut cores = self.cores.lock().unwrap();
+        let package = match cores.get(&cpu) {
+            Some(core) => core.package,
+            None => return,
+        };
+        for (id, core) in cores.iter_mut() {
+            core.advance(now);
+            if *id == cpu {
+                core.core_power = core.core_power.map(|_| core_power);
+            }
+            if core.package == package {
+                core.package_power = core.package_power.map(|_| package_power);
+            }
+        }
+    }
+
+    /// Make the core energy domain of `cpu` unavailable, as on platforms where it can't be read on some cores
+    pub fn disable_core_energy(&self, cpu: u32) {
+        if let Some(core) = self.cores.lock().unwrap().get_mut(&cpu) {
+            core.advance(self.clock.now());
+            core.core_power = None;
+        }
+    }
+
+    fn read(&self, cpu: u32, energy: impl Fn(&MockCore) -> Option<f64>) -> io::Result<u64> {
+        let mut cores = self.cores.lock().unwrap();
+        let core = cores
+            .get_mut(&cpu)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        core.advance(self.clock.now());
+        let joule = energy(core).ok_or_else(|| io::Error::from_raw_os_error(libc::ENXIO))?;
+        Ok((joule * f64::from(1u32 << MOCK_ENERGY_UNIT_BITS)) as u64 & 0xFFFF_FFFF)
+    }
+}
+
+impl PowerBackend for MockBackend {
+    fn cpus(&self) -> Result<Vec<(u32, u32)>, Error> {
+        Ok(self
+            .cores
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(cpu, core)| (*cpu, core.package))
+            .collect())
+    }
+
+    fn read_units(&self, _cpu: u32) -> io::Result<u64> {
+        Ok(0x000A_0003 | (MOCK_ENERGY_UNIT_BITS as u64) << 8)
+    }
+
+    fn read_core_energy(&self, cpu: u32) -> io::Result<u64> {
+        self.read(cpu, |core| core.core_power.map(|_| core.core_energy))
+    }
+
+    fn read_package_energy(&self, cpu: u32) -> io::Result<u64> {
+        self.read(cpu, |core| core.package_power.map(|_| core.package_energy))
+    }
+}
+
+impl<B: PowerBackend + ?Sized> PowerBackend for std::sync::Arc<B> {
+    fn cpus(&self) -> Result<Vec<(u32, u32)>, Error> {
+        (**self).cpus()
+    }
+
+    fn read_units(&self, cpu: u32) -> io::Result<u64> {
+        (**self).read_units(cpu)
+    }
+
+    fn read_core_energy(&self, cpu: u32) -> io::Result<u64> {
+        (**self).read_core_energy(cpu)
+    }
+
+    fn read_package_energy(&self, cpu: u32) -> io::Result<u64> {
+        (**self).read_package_energy(cpu)
+    }
+}
@@ -0,0 +1,190 @@
+//! Comparing the latency and agreement of the available counter sources
+//!
+//! Every source is opened separately, the time to take a snapshot gives its latency and the package power
+//! measured by all sources over the same window gives their agreement. The recommended source can be saved
+//! and picked up by later runs with [`CpuInfoBuilder::saved_source`](crate::CpuInfoBuilder::saved_source).
+
+use crate::{CpuInfo, CpuPower, Error, Source};
+use std::fs::{read_to_string, write};
+use std::io;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// The number of snapshots the latency of a source is averaged over
+const LATENCY_READS: u32 = 16;
+
+/// Sources whose package power differs more than this fraction from the median of all sources are not recommended
+const AGREEMENT_TOLERANCE: f64 = 0.05;
+
+/// First line of a saved source choice, including the format version
+const SOURCE_HEADER: &str = "ryzen-reader-source 1";
+
+/// The benchmark results of a single source
+#[derive(Debug, Clone)]
+pub struct SourceBenchmark {
+    pub source: Source,
+    /// The name of the source as reported by [`CpuInfo::source`]
+    pub name: &'static str,
+    /// The average time to take a snapshot of all counters
+    pub latency: Duration,
+    /// The total package power measured over the benchmark window in watt
+    pub package_power: f64,
+    /// The relative difference between the package power and the median of all sources
+    pub deviation: f64,
+}
+
+/// The results of [`CpuInfo::benchmark_backends`]
+#[derive(Debug, Clone)]
+pub struct BackendBenchmark {
+    /// The results of every source that could be opened and read
+    pub sources: Vec<SourceBenchmark>,
+    recommended: Option<usize>,
+}
+
+impl BackendBenchmark {
+    /// The source with the lowest latency among the sources that agree with the median of all sources
+    ///
+    /// `None` if no source is within 5% of the median, which happens when only two sources were found that don't agree.
+    pub fn recommended(&self) -> Option<&SourceBenchmark> {
+        self.recommended.map(|index| &self.sources[index])
+    }
+
+    /// Save the recommended source, to be used by later runs through [`CpuInfoBuilder::saved_source`](crate::CpuInfoBuilder::saved_source)
+    ///
+    /// Fails if there is no recommendation or a custom backend is recommended.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        match self.recommended() {
+            Some(recommended) => save_source(&recommended.source, path),
+            None => Err(Error::SourceUnavailable),
+        }
+    }
+}
+
+/// Save a source choice to a file
+///
+/// Custom backends can't be saved.
+pub fn save_source<P: AsRef<Path>>(source: &Source, path: P) -> Result<(), Error> {
+    let name = match source {
+        Source::Msr => "msr",
+        Source::Sysfs => "sysfs",
+        Source::Backend(_) => {
+            return Err(Error::IO(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "custom backends can't be saved",
+            )))
+        }
+    };
+    write(path, format!("{}\n{}\n", SOURCE_HEADER, name)).map_err(Error::IO)
+}
+
+/// Load a source choice saved with [`save_source`] or [`BackendBenchmark::save`]
+///
+/// Returns `None` if there is no saved choice or the file is not a saved choice.
+pub fn load_source<P: AsRef<Path>>(path: P) -> Result<Option<Source>, Error> {
+    let saved = match read_to_string(path) {
+        Ok(saved) => saved,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::IO(e)),
+    };
+    let mut lines = saved.lines();
+    if lines.next() != Some(SOURCE_HEADER) {
+        return Ok(None);
+    }
+    Ok(match lines.next() {
+        Some("msr") => Some(Source::Msr),
+        Some("sysfs") => Some(Source::Sysfs),
+        _ => None,
+    })
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+impl CpuInfo {
+    /// Measure the latency and agreement of the msr and sysfs sources available on this machine
+    ///
+    /// The package power is compared over `interval`, see [`benchmark_sources`](CpuInfo::benchmark_sources).
+    pub fn benchmark_backends(interval: Duration) -> Result<BackendBenchmark, Error> {
+        Self::benchmark_sources(&[Source::Sysfs, Source::Msr], interval)
+    }
+
+    /// Measure the latency and agreement of the given sources
+    ///
+    /// Sources that fail to open or read are left out of the results,
+    /// [`Error::SourceUnavailable`] is returned when none of the sources can be used.
+    pub fn benchmark_sources(
+        sources: &[Source],
+        interval: Duration,
+    ) -> Result<BackendBenchmark, Error> {
+        if interval.is_zero() {
+            return Err(Error::EmptyInterval);
+        }
+        let mut opened: Vec<(Source, CpuInfo, Duration)> = sources
+            .iter()
+            .filter_map(|source| {
+                let cpu = CpuInfo::builder().source(source.clone()).build().ok()?;
+                let start = Instant::now();
+                for _ in 0..LATENCY_READS {
+                    cpu.snapshot().ok()?;
+                }
+                Some((source.clone(), cpu, start.elapsed() / LATENCY_READS))
+            })
+            .collect();
+        if let Some(max) = opened.iter().map(|(_, cpu, _)| cpu.max_interval()).min() {
+            if interval > max {
+                return Err(Error::IntervalTooLong { interval, max });
+            }
+        }
+
+        let starts: Vec<_> = opened.iter().map(|(_, cpu, _)| cpu.snapshot()).collect();
+        sleep(interval);
+        let mut results = Vec::with_capacity(opened.len());
+        for ((source, cpu, latency), start) in opened.drain(..).zip(starts) {
+            let power = start
+                .and_then(|start| CpuPower::between(&start, &cpu.snapshot()?))
+                .map(|power| power.packages().sum::<f64>());
+            if let Ok(package_power) = power {
+                results.push(SourceBenchmark {
+                    source,
+                    name: cpu.source(),
+                    latency,
+                    package_power,
+                    deviation: 0.0,
+                });
+            }
+        }
+        if results.is_empty() {
+            return Err(Error::SourceUnavailable);
+        }
+
+        let powers: Vec<f64> = results.iter().map(|result| result.package_power).collect();
+        let median = median(&powers);
+        for result in &mut results {
+            result.deviation = if median == 0.0 {
+                0.0
+            } else {
+                (result.package_power - median).abs() / median
+            };
+        }
+        let recommended = results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| result.deviation <= AGREEMENT_TOLERANCE)
+            .min_by_key(|(_, result)| result.latency)
+            .map(|(index, _)| index);
+
+        Ok(BackendBenchmark {
+            sources: results,
+            recommended,
+        })
+    }
+}
@@ -0,0 +1,70 @@
+use ryzen_reader::process::Energy;
+use ryzen_reader::{CpuInfo, Error};
+use std::env::args;
+use std::process::{exit, Command};
+
+const USAGE: &str = "Usage: cargo energy [--runs <n>] [--] [command [args...]]
+
+Runs the command, or `cargo bench` if no command is given, and reports the package energy used";
+
+fn main() -> Result<(), Error> {
+    let mut args = args().skip(1).peekable();
+    // when invoked as `cargo energy` cargo passes the subcommand name as the first argument
+    if args.peek().map(String::as_str) == Some("energy") {
+        args.next();
+    }
+
+    let mut runs = 1;
+    let mut command = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--runs" => match args.next().and_then(|runs| runs.parse().ok()) {
+                Some(count) if count > 0 => runs = count,
+                _ => {
+                    eprintln!("{}", USAGE);
+                    exit(1);
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--" => {
+                command.extend(args.by_ref());
+            }
+            _ => {
+                command.push(arg);
+                command.extend(args.by_ref());
+            }
+        }
+    }
+    if command.is_empty() {
+        command = vec!["cargo".to_string(), "bench".to_string()];
+    }
+
+    let cpu = CpuInfo::new()?;
+    let mut total = 0.0;
+    for run in 1..=runs {
+        let mut cmd = Command::new(&command[0]);
+        cmd.args(&command[1..]);
+        let energy = Energy::of_with(&cpu, cmd)?;
+        println!(
+            "run #{}: {:.2}J in {:.2}s, average {:.2}W, peak {:.2}W",
+            run,
+            energy.energy,
+            energy.duration.as_secs_f64(),
+            energy.energy / energy.duration.as_secs_f64(),
+            energy.peak
+        );
+        if !energy.status.success() {
+            eprintln!("command failed: {}", energy.status);
+            exit(energy.status.code().unwrap_or(1));
+        }
+        total += energy.energy;
+    }
+
+    if runs > 1 {
+        println!("mean: {:.2}J per run", total / runs as f64);
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use ryzen_reader::descriptor::FamilyDescriptor;
+use ryzen_reader::sweep::SweepConfig;
+use ryzen_reader::{CpuInfo, CpuInfoBuilder, CpuPower, Error, NoisePolicy, UnitOverrides};
+use std::env::args;
+use std::process::exit;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: ryzen-reader [--interval <ms>] [--count <n>] [--watch] [--noise-policy <policy>]
+       ryzen-reader sweep [--cores <n,...>] [--steps <n>] [--settle <ms>] [--measure <ms>]
+
+Prints the package and core power averaged over every interval,
+or with `sweep` the package power while stepping a generated load from 0 to 100%
+
+Options:
+    --interval <ms>  length of every reading in milliseconds, defaults to 1000
+    --count <n>      number of readings to print, defaults to 1
+    --watch          keep printing readings until interrupted, clearing the screen between readings
+    --noise-policy <policy>
+                     cleanup of noisy idle core values, `none` to leave them untouched (the default),
+                     `clamp` to clamp negative values to zero or a threshold in watt below which values
+                     are also reported as zero
+
+Sweep options:
+    --cores <n,...>  comma separated numbers of cpus to load, defaults to all cpus
+    --steps <n>      number of load levels from 0 to 100%, defaults to 11
+    --settle <ms>    time to wait after changing the load, defaults to 500
+    --measure <ms>   length of the reading of every load level, defaults to 1000
+
+Environment:
+    RYZEN_READER_ENERGY_UNIT, RYZEN_READER_TIME_UNIT, RYZEN_READER_POWER_UNIT
+                     override the unit exponents reported by the cpu
+    RYZEN_READER_DESCRIPTOR
+                     file with msr addresses and encodings for cpus that aren't supported yet";
+
+/// Escape sequence to clear the terminal and move the cursor to the top left
+const CLEAR: &str = "\x1b[2J\x1b[H";
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    exit(1);
+}
+
+fn parse_noise_policy(policy: &str) -> Option<NoisePolicy> {
+    match policy {
+        "none" => Some(NoisePolicy::default()),
+        "clamp" => Some(NoisePolicy {
+            clamp_negative: true,
+            zero_below: None,
+        }),
+        threshold => match threshold.parse() {
+            Ok(watt) if watt >= 0.0 => Some(NoisePolicy::denoise(watt)),
+            _ => None,
+        },
+    }
+}
+
+fn print_power(power: &CpuPower) {
+    println!("Package power:");
+    for (package, usage) in power.packages().enumerate() {
+        println!("\t#{}: {:.2}W", package, usage);
+    }
+    println!("Core power:");
+    for core in power.core_readings() {
+        println!("\t#{}: {:.2}W", core.cpu, core.power);
+    }
+}
+
+fn builder() -> Result<CpuInfoBuilder, Error> {
+    let builder = CpuInfo::builder().unit_overrides(UnitOverrides::from_env());
+    Ok(match std::env::var_os("RYZEN_READER_DESCRIPTOR") {
+        Some(path) => builder.descriptor(FamilyDescriptor::load(path)?),
+        None => builder,
+    })
+}
+
+fn parse_ms(ms: Option<String>) -> Duration {
+    match ms.and_then(|ms| ms.parse().ok()) {
+        Some(ms) => Duration::from_millis(ms),
+        None => usage_error(),
+    }
+}
+
+fn sweep(mut args: impl Iterator<Item = String>) -> Result<(), Error> {
+    let mut config = SweepConfig::default();
+    while let Some(arg) = args.next() {
+        config = match arg.as_str() {
+            "--cores" => match args.next().and_then(|cores| {
+                cores
+                    .split(',')
+                    .map(|count| count.parse().ok())
+                    .collect::<Option<Vec<usize>>>()
+            }) {
+                Some(cores) => config.core_counts(&cores),
+                None => usage_error(),
+            },
+            "--steps" => match args.next().and_then(|steps| steps.parse().ok()) {
+                Some(steps) => config.steps(steps),
+                None => usage_error(),
+            },
+            "--settle" => config.settle(parse_ms(args.next())),
+            "--measure" => config.measure(parse_ms(args.next())),
+            _ => usage_error(),
+        };
+    }
+
+    let sweep = builder()?.build()?.sweep(&config)?;
+    for (index, package) in sweep.packages().into_iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        println!("Package #{}:", package);
+        println!("	cores	load	power");
+        for (cores, load, power) in sweep.curve(package) {
+            println!("	{}	{:.0}%	{:.2}W", cores, load * 100.0, power);
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let mut args = args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("sweep") {
+        args.next();
+        return sweep(args);
+    }
+
+    let mut interval = Duration::from_millis(1000);
+    let mut count = Some(1);
+    let mut watch = false;
+    let mut noise_policy = NoisePolicy::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interval" => match args.next().and_then(|ms| ms.parse().ok()) {
+                Some(ms) if ms > 0 => interval = Duration::from_millis(ms),
+                _ => usage_error(),
+            },
+            "--count" => match args.next().and_then(|count| count.parse().ok()) {
+                Some(n) if n > 0 => count = Some(n),
+                _ => usage_error(),
+            },
+            "--watch" => watch = true,
+            "--noise-policy" => match args.next().as_deref().and_then(parse_noise_policy) {
+                Some(policy) => noise_policy = policy,
+                None => usage_error(),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => usage_error(),
+        }
+    }
+    if watch {
+        count = None;
+    }
+
+    let cpu = builder()?.noise_policy(noise_policy).build()?;
+    let mut printed = 0;
+    while count.is_none_or(|count| printed < count) {
+        let power = cpu.read_with_interval(interval)?;
+        if watch {
+            print!("{}", CLEAR);
+        } else if printed > 0 {
+            println!();
+        }
+        print_power(&power);
+        printed += 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! Preferred core rankings and how the power is spread over the ccds
+//!
+//! On parts with multiple ccds the firmware ranks the cores by how well they boost, the scheduler places
+//! single threaded work on the highest ranked cores, which concentrates the power on the ccd containing them.
+//! The rankings are taken from amd-pstate when it is active, or from the ACPI CPPC highest performance otherwise.
+
+use crate::sysfs::read_value;
+use crate::{CpuInfo, CpuPower};
+use std::collections::BTreeMap;
+
+/// The boost preference and power of a single ccd
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct CcdPreference {
+    pub package: u32,
+    pub ccd: u32,
+    /// The ranking of the highest ranked core of the ccd, `None` if no rankings are available
+    pub ranking: Option<u32>,
+    /// Whether the ccd contains the highest ranked core of the package
+    pub preferred: bool,
+    /// The combined power of the physical cores of the ccd in watt
+    pub power: f64,
+}
+
+/// The preferred core ranking of a cpu, higher is better
+pub(crate) fn core_ranking(cpu: u32) -> Option<u32> {
+    let base = format!("/sys/devices/system/cpu/cpu{}", cpu);
+    read_value(format!("{}/cpufreq/amd_pstate_prefcore_ranking", base))
+        .or_else(|_| read_value(format!("{}/acpi_cppc/highest_perf", base)))
+        .ok()
+}
+
+impl CpuInfo {
+    /// Combine the preferred core rankings with the core power of a reading, grouped by ccd
+    ///
+    /// The rankings can change at runtime and are read on every call.
+    /// Cores without a known ccd are left out, ccds are ordered by package and ccd.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let cpu = CpuInfo::new()?;
+    ///     let power = cpu.read()?;
+    ///     for ccd in cpu.ccd_preferences(&power) {
+    ///         println!("ccd {}: {:.2}W{}", ccd.ccd, ccd.power, if ccd.preferred { " (preferred)" } else { "" });
+    ///     }
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn ccd_preferences(&self, power: &CpuPower) -> Vec<CcdPreference> {
+        let mut ccds: BTreeMap<(u32, u32), CcdPreference> = BTreeMap::new();
+
+        for core in &power.cores {
+            let ccd = match core.ccd {
+                Some(ccd) => ccd,
+                None => continue,
+            };
+            let entry = ccds
+                .entry((core.package, ccd))
+                .or_insert_with(|| CcdPreference {
+                    package: core.package,
+                    ccd,
+                    ranking: None,
+                    preferred: false,
+                    power: 0.0,
+                });
+            entry.ranking = entry.ranking.max(core_ranking(core.cpu));
+        }
+        for ccd in power.ccds() {
+            if let Some(entry) = ccds.get_mut(&(ccd.package, ccd.ccd)) {
+                entry.power = ccd.power;
+            }
+        }
+
+        let mut best: BTreeMap<u32, u32> = BTreeMap::new();
+        for ccd in ccds.values() {
+            if let Some(ranking) = ccd.ranking {
+                let best = best.entry(ccd.package).or_default();
+                *best = (*best).max(ranking);
+            }
+        }
+        ccds.into_values()
+            .map(|mut ccd| {
+                ccd.preferred =
+                    ccd.ranking.is_some() && ccd.ranking == best.get(&ccd.package).copied();
+                ccd
+            })
+            .collect()
+    }
+}
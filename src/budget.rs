@@ -0,0 +1,155 @@
+use crate::{CpuInfo, CpuPower, Error};
+
+/// An action a [`BudgetPolicy`] can request after looking at a sample
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum BudgetAction {
+    /// Send `signal` to the process with id `pid`, e.g. `SIGSTOP`/`SIGCONT` to pause and resume a workload
+    Signal { pid: i32, signal: i32 },
+    /// Set the long term power limit of a package in watt through powercap
+    #[cfg(feature = "limits")]
+    SetPackageLimit { package: u32, watt: f64 },
+}
+
+impl BudgetAction {
+    fn apply(&self) -> Result<(), Error> {
+        match *self {
+            #[cfg(unix)]
+            BudgetAction::Signal { pid, signal } => {
+                if unsafe { libc::kill(pid, signal) } == 0 {
+                    Ok(())
+                } else {
+                    Err(Error::Signal(pid, std::io::Error::last_os_error()))
+                }
+            }
+            #[cfg(not(unix))]
+            BudgetAction::Signal { pid, .. } => {
+                Err(Error::Signal(pid, std::io::ErrorKind::Unsupported.into()))
+            }
+            #[cfg(feature = "limits")]
+            BudgetAction::SetPackageLimit { package, watt } => {
+                crate::powercap::set_package_limit(package, watt)
+            }
+        }
+    }
+}
+
+/// A power budgeting policy
+///
+/// The policy gets to see every sample taken by a [`BudgetEnforcer`] and returns the actions it wants applied.
+pub trait BudgetPolicy {
+    fn sample(&mut self, power: &CpuPower) -> Vec<BudgetAction>;
+}
+
+impl<F: FnMut(&CpuPower) -> Vec<BudgetAction>> BudgetPolicy for F {
+    fn sample(&mut self, power: &CpuPower) -> Vec<BudgetAction> {
+        self(power)
+    }
+}
+
+/// Policy that pauses a process while the total package power is over a limit
+///
+/// The process is stopped with `SIGSTOP` once the limit is exceeded and resumed with `SIGCONT`
+/// as soon as the power draw drops below the limit again.
+/// A process stopped by the policy is also resumed when the policy is dropped,
+/// so an error or panic in the enforcer doesn't leave it frozen.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct ThrottleProcess {
+    pid: i32,
+    limit: f64,
+    stopped: bool,
+}
+
+#[cfg(unix)]
+impl ThrottleProcess {
+    /// Throttle process `pid` whenever the total package power exceeds `limit` watt
+    pub fn new(pid: i32, limit: f64) -> Self {
+        ThrottleProcess {
+            pid,
+            limit,
+            stopped: false,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl BudgetPolicy for ThrottleProcess {
+    fn sample(&mut self, power: &CpuPower) -> Vec<BudgetAction> {
+        let total: f64 = power.packages().sum();
+        if total > self.limit && !self.stopped {
+            self.stopped = true;
+            vec![BudgetAction::Signal {
+                pid: self.pid,
+                signal: libc::SIGSTOP,
+            }]
+        } else if total <= self.limit && self.stopped {
+            self.stopped = false;
+            vec![BudgetAction::Signal {
+                pid: self.pid,
+                signal: libc::SIGCONT,
+            }]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ThrottleProcess {
+    fn drop(&mut self) {
+        if self.stopped {
+            unsafe {
+                libc::kill(self.pid, libc::SIGCONT);
+            }
+        }
+    }
+}
+
+/// Closed loop power budgeting
+///
+/// Each call to [`step`](BudgetEnforcer::step) takes a sample, hands it to the policy and applies the requested actions.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::{CpuInfo, Error};
+/// # use ryzen_reader::budget::{BudgetEnforcer, ThrottleProcess};
+/// #
+/// # fn main() -> Result<(), Error> {
+///     let pid = 1234;
+///     let mut enforcer = BudgetEnforcer::new(CpuInfo::new()?, ThrottleProcess::new(pid, 65.0));
+///     loop {
+///         enforcer.step()?;
+///     }
+/// # }
+///```
+pub struct BudgetEnforcer<P> {
+    cpu: CpuInfo,
+    policy: P,
+}
+
+impl<P: BudgetPolicy> BudgetEnforcer<P> {
+    pub fn new(cpu: CpuInfo, policy: P) -> Self {
+        BudgetEnforcer { cpu, policy }
+    }
+
+    /// Take a sample, pass it to the policy and apply the actions the policy requested
+    ///
+    /// Note that this method will block for the duration of [`CpuInfo::read`]
+    pub fn step(&mut self) -> Result<CpuPower, Error> {
+        let power = self.cpu.read()?;
+        for action in self.policy.sample(&power) {
+            action.apply()?;
+        }
+        Ok(power)
+    }
+
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    pub fn into_inner(self) -> (CpuInfo, P) {
+        (self.cpu, self.policy)
+    }
+}
@@ -0,0 +1,47 @@
+//! Convert measured energy into carbon emissions
+//!
+//! The grid carbon intensity has to be supplied by the user, either directly or through the
+//! `RYZEN_READER_CARBON_INTENSITY` environment variable, for example from a grid operator api.
+
+use std::env::var;
+
+const JOULE_PER_KWH: f64 = 3_600_000.0;
+
+/// Carbon intensity of the electricity used, in grams of CO2 equivalent per kWh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CarbonIntensity {
+    grams_per_kwh: f64,
+}
+
+impl CarbonIntensity {
+    pub fn new(grams_per_kwh: f64) -> Self {
+        CarbonIntensity { grams_per_kwh }
+    }
+
+    /// Read the carbon intensity from the `RYZEN_READER_CARBON_INTENSITY` environment variable
+    pub fn from_env() -> Option<Self> {
+        var("RYZEN_READER_CARBON_INTENSITY")
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+            .map(CarbonIntensity::new)
+    }
+
+    pub fn grams_per_kwh(&self) -> f64 {
+        self.grams_per_kwh
+    }
+
+    /// The emissions for using `energy` joule, in grams of CO2 equivalent
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use ryzen_reader::carbon::CarbonIntensity;
+    /// let intensity = CarbonIntensity::new(400.0);
+    /// assert_eq!(intensity.emissions(3_600_000.0), 400.0);
+    /// ```
+    pub fn emissions(&self, energy: f64) -> f64 {
+        energy / JOULE_PER_KWH * self.grams_per_kwh
+    }
+}
@@ -0,0 +1,75 @@
+//! Time sources for the interval measurements
+//!
+//! Power is calculated from the energy used between two snapshots and the time between them as reported by a [`Clock`].
+//! The default [`SystemClock`] uses the monotonic system clock, a [`ManualClock`] allows tests to control time.
+
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A source of timestamps for snapshots
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic system clock, which isn't affected by changes to the wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to
+///
+/// Can be shared with a [`MockBackend`](crate::backend::MockBackend) to get exact, reproducible readings.
+#[derive(Debug)]
+pub struct ManualClock {
+    created: Instant,
+    now: Mutex<Instant>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        let created = Instant::now();
+        ManualClock {
+            created,
+            now: Mutex::new(created),
+        }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    /// Move the clock backward, as a misbehaving time source might
+    ///
+    /// The clock can't be moved back further than the time it was created at.
+    pub fn rewind(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now = now
+            .checked_sub(duration)
+            .map_or(self.created, |rewound| rewound.max(self.created));
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for std::sync::Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
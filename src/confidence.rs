@@ -0,0 +1,161 @@
+//! Readings with an estimate of how much the power varied within the sampling window
+//!
+//! The window is split into equal sub-intervals, the spread of the power over the sub-intervals gives an estimate of
+//! how reliable the average over the whole window is. Readings of a steady load get a small error,
+//! readings during load changes or with few counter updates get a large one.
+
+use crate::{find_cpu, package_values, CpuInfo, CpuPower, Error};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// The shortest sub-interval, the counters only update about every millisecond
+const MIN_SUBINTERVAL: Duration = Duration::from_millis(1);
+
+/// The average power over a window with the spread of the power over the sub-intervals
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PowerEstimate {
+    /// The average power over the whole window in watt
+    pub power: f64,
+    /// The standard deviation of the power over the sub-intervals in watt
+    pub std_dev: f64,
+    /// The standard error of the average power in watt
+    pub std_error: f64,
+}
+
+impl PowerEstimate {
+    fn new(power: f64, samples: &[f64]) -> Self {
+        let count = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / count;
+        let variance = samples
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f64>()
+            / (count - 1.0).max(1.0);
+        let std_dev = variance.sqrt();
+        PowerEstimate {
+            power,
+            std_dev,
+            std_error: std_dev / count.sqrt(),
+        }
+    }
+
+    /// The approximate 95% confidence interval of the average power in watt
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        let margin = 1.96 * self.std_error;
+        (self.power - margin, self.power + margin)
+    }
+}
+
+/// A power reading with an estimate for every core and package
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SampledPower {
+    /// The reading over the whole window
+    pub power: CpuPower,
+    /// The estimate for every core, in the same order as [`CpuPower::cores`]
+    pub cores: Vec<PowerEstimate>,
+    /// The estimate for every package, in the same order as [`CpuPower::packages`]
+    pub packages: Vec<PowerEstimate>,
+    /// The number of sub-intervals the window was split in
+    pub subsamples: u32,
+}
+
+/// The estimates for every cpu or package id, from the reading over the whole window and the readings of the sub-intervals
+///
+/// Sub-intervals are matched by id since a core can be skipped in some of them, shifting the positions of the others.
+fn estimates(total: Vec<(u32, f64)>, samples: &[Vec<(u32, f64)>]) -> Vec<PowerEstimate> {
+    total
+        .into_iter()
+        .enumerate()
+        .map(|(index, (id, power))| {
+            let samples: Vec<f64> = samples
+                .iter()
+                .filter_map(|sample| find_cpu(sample, index, id, |(id, _)| *id))
+                .map(|(_, power)| *power)
+                .collect();
+            PowerEstimate::new(power, &samples)
+        })
+        .collect()
+}
+
+fn cores_by_cpu(power: &CpuPower) -> Vec<(u32, f64)> {
+    power
+        .core_readings()
+        .map(|reading| (reading.cpu, reading.power))
+        .collect()
+}
+
+fn packages_by_id(power: &CpuPower) -> Vec<(u32, f64)> {
+    package_values(&power.cores, power.package_aggregation).collect()
+}
+
+impl CpuInfo {
+    /// Read the power over `interval`, with an estimate of the variance from `subsamples` sub-intervals
+    ///
+    /// At least two sub-intervals are used. The counters only update about every millisecond,
+    /// sub-intervals shorter than a few milliseconds mostly measure the update jitter.
+    /// Zero sub-intervals or sub-intervals shorter than a millisecond are rejected with [`Error::InvalidSubsamples`].
+    ///
+    /// Intervals longer than [`max_interval`](CpuInfo::max_interval) are rejected with [`Error::IntervalTooLong`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// # use std::time::Duration;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let cpu = CpuInfo::new()?;
+    ///     let sampled = cpu.read_with_confidence(Duration::from_secs(1), 10)?;
+    ///     for package in &sampled.packages {
+    ///         println!("{:.2}W ± {:.2}W", package.power, 1.96 * package.std_error);
+    ///     }
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn read_with_confidence(
+        &self,
+        interval: Duration,
+        subsamples: u32,
+    ) -> Result<SampledPower, Error> {
+        let max = self.max_interval();
+        if interval > max {
+            return Err(Error::IntervalTooLong { interval, max });
+        }
+        if interval.is_zero() {
+            return Err(Error::EmptyInterval);
+        }
+        let max_subsamples =
+            (interval.as_nanos() / MIN_SUBINTERVAL.as_nanos()).clamp(2, u32::MAX.into()) as u32;
+        if subsamples == 0 || subsamples > max_subsamples {
+            return Err(Error::InvalidSubsamples {
+                subsamples,
+                max: max_subsamples,
+            });
+        }
+
+        let subsamples = subsamples.max(2);
+        let step = interval / subsamples;
+        let start = self.snapshot()?;
+        let mut last = start.clone();
+        let mut cores = Vec::with_capacity(subsamples as usize);
+        let mut packages = Vec::with_capacity(subsamples as usize);
+        for _ in 0..subsamples {
+            sleep(step);
+            let snapshot = self.snapshot()?;
+            let power = CpuPower::between(&last, &snapshot)?;
+            cores.push(cores_by_cpu(&power));
+            packages.push(packages_by_id(&power));
+            last = snapshot;
+        }
+
+        let power = CpuPower::between(&start, &last)?;
+        Ok(SampledPower {
+            cores: estimates(cores_by_cpu(&power), &cores),
+            packages: estimates(packages_by_id(&power), &packages),
+            power,
+            subsamples,
+        })
+    }
+}
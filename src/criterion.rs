@@ -0,0 +1,126 @@
+//! Package energy as a [criterion](https://docs.rs/criterion) measurement
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use criterion::{criterion_group, criterion_main, Criterion};
+//! use ryzen_reader::criterion::PackageEnergy;
+//!
+//! fn bench(c: &mut Criterion<PackageEnergy>) {
+//!     c.bench_function("sum", |b| b.iter(|| (0..1000u64).sum::<u64>()));
+//! }
+//!
+//! fn energy() -> Criterion<PackageEnergy> {
+//!     Criterion::default().with_measurement(PackageEnergy::new().unwrap())
+//! }
+//!
+//! criterion_group! {
+//!     name = benches;
+//!     config = energy();
+//!     targets = bench
+//! }
+//! criterion_main!(benches);
+//! ```
+
+use crate::{CpuInfo, Error};
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::Throughput;
+
+/// Measures the total package energy in joule
+///
+/// Note that the energy of everything running on the system is measured and that the energy counters
+/// only update every millisecond or so, benchmarks need enough iterations per sample to get meaningful values.
+pub struct PackageEnergy {
+    cpu: CpuInfo,
+}
+
+impl PackageEnergy {
+    pub fn new() -> Result<Self, Error> {
+        Ok(PackageEnergy {
+            cpu: CpuInfo::new()?,
+        })
+    }
+
+    pub fn with_cpu(cpu: CpuInfo) -> Self {
+        PackageEnergy { cpu }
+    }
+
+    fn energy(&self) -> f64 {
+        self.cpu
+            .energy_since_init()
+            .expect("failed to read package energy")
+            .packages()
+            .sum()
+    }
+}
+
+impl Measurement for PackageEnergy {
+    type Intermediate = f64;
+    type Value = f64;
+
+    fn start(&self) -> Self::Intermediate {
+        self.energy()
+    }
+
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        self.energy() - start
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0.0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &JouleFormatter
+    }
+}
+
+struct JouleFormatter;
+
+impl ValueFormatter for JouleFormatter {
+    fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+        let (factor, unit) = if typical_value < 1e-6 {
+            (1e9, "nJ")
+        } else if typical_value < 1e-3 {
+            (1e6, "µJ")
+        } else if typical_value < 1.0 {
+            (1e3, "mJ")
+        } else {
+            (1.0, "J")
+        };
+
+        for value in values {
+            *value *= factor;
+        }
+        unit
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let (count, unit) = match *throughput {
+            Throughput::Bytes(bytes) | Throughput::BytesDecimal(bytes) => (bytes, "B/J"),
+            Throughput::Elements(elements) => (elements, "elem/J"),
+        };
+
+        for value in values {
+            *value = count as f64 / *value;
+        }
+        unit
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "J"
+    }
+}
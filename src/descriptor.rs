@@ -0,0 +1,222 @@
+//! How the msrs of a cpu family are addressed and decoded
+//!
+//! The registers and encodings used for a cpu are described by a [`FamilyDescriptor`], picked once when the
+//! [`CpuInfo`](crate::CpuInfo) is created. Supporting a new family means adding it to [`FamilyDescriptor::for_cpu`]
+//! instead of special casing it in the read path.
+//!
+//! For cpus that aren't known yet the builtin descriptor can be overridden with a file,
+//! see [`FamilyDescriptor::load`] for the format.
+
+use crate::support::{current_cpu, generation, Generation};
+use crate::Error;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// How a frequency is encoded in the P-state msrs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FrequencyEncoding {
+    /// A frequency id in 200MHz steps divided by a frequency divisor, used up to Zen 4
+    FidDivisor,
+    /// A frequency id in 5MHz steps, used by Zen 5
+    Fid5Mhz,
+}
+
+/// How a voltage is encoded in the P-state msrs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VoltageEncoding {
+    /// SVI2, 6.25mV steps down from 1.55V
+    Svi2,
+    /// SVI3, 5mV steps up from 0.245V
+    Svi3,
+    /// The encoding isn't known, no voltages are reported
+    Unknown,
+}
+
+/// The msr addresses and encodings of a cpu family
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FamilyDescriptor {
+    /// Address of the msr containing the time, energy and power units
+    pub power_unit: u64,
+    /// Address of the per core energy counter
+    pub core_energy: u64,
+    /// Address of the package energy counter
+    pub package_energy: u64,
+    /// The bits of the power unit msr containing the time unit exponent
+    pub time_unit_mask: u64,
+    /// The bits of the power unit msr containing the energy unit exponent
+    pub energy_unit_mask: u64,
+    /// The bits of the power unit msr containing the power unit exponent
+    pub power_unit_mask: u64,
+    pub frequency: FrequencyEncoding,
+    pub voltage: VoltageEncoding,
+}
+
+/// The layout shared by all families since Zen
+const ZEN: FamilyDescriptor = FamilyDescriptor {
+    power_unit: 0xC001_0299,
+    core_energy: 0xC001_029A,
+    package_energy: 0xC001_029B,
+    time_unit_mask: 0xF_0000,
+    energy_unit_mask: 0x1F00,
+    power_unit_mask: 0xF,
+    frequency: FrequencyEncoding::FidDivisor,
+    voltage: VoltageEncoding::Svi2,
+};
+
+impl Default for FamilyDescriptor {
+    fn default() -> Self {
+        ZEN
+    }
+}
+
+/// Decode a field of a register described by a mask, an empty mask decodes as 0
+pub(crate) fn field(value: u64, mask: u64) -> u64 {
+    (value & mask)
+        .checked_shr(mask.trailing_zeros())
+        .unwrap_or(0)
+}
+
+/// Whether a mask selects a single non empty run of bits
+fn is_contiguous(mask: u64) -> bool {
+    let shifted = field(mask, mask);
+    mask != 0 && shifted & shifted.wrapping_add(1) == 0
+}
+
+impl FamilyDescriptor {
+    /// The builtin descriptor for a cpu family and model
+    ///
+    /// Unknown parts get the Zen layout without voltage decoding.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use ryzen_reader::descriptor::{FamilyDescriptor, FrequencyEncoding, VoltageEncoding};
+    /// #
+    /// assert_eq!(FamilyDescriptor::for_cpu(0x19, 0x61).voltage, VoltageEncoding::Svi3);
+    /// assert_eq!(FamilyDescriptor::for_cpu(0x1A, 0x44).frequency, FrequencyEncoding::Fid5Mhz);
+    ///```
+    pub fn for_cpu(family: u32, model: u32) -> Self {
+        match generation(family, model) {
+            Some(Generation::Zen4) => FamilyDescriptor {
+                voltage: VoltageEncoding::Svi3,
+                ..ZEN
+            },
+            Some(Generation::Zen5) => FamilyDescriptor {
+                frequency: FrequencyEncoding::Fid5Mhz,
+                voltage: VoltageEncoding::Unknown,
+                ..ZEN
+            },
+            Some(_) => ZEN,
+            None => FamilyDescriptor {
+                frequency: if family >= 0x1A {
+                    FrequencyEncoding::Fid5Mhz
+                } else {
+                    FrequencyEncoding::FidDivisor
+                },
+                voltage: VoltageEncoding::Unknown,
+                ..ZEN
+            },
+        }
+    }
+
+    /// The builtin descriptor for the cpu the code is running on
+    pub fn current() -> Self {
+        match current_cpu() {
+            Some((family, model)) => Self::for_cpu(family, model),
+            None => FamilyDescriptor {
+                voltage: VoltageEncoding::Unknown,
+                ..ZEN
+            },
+        }
+    }
+
+    /// Load the descriptor for the running cpu with the overrides from a file
+    ///
+    /// The file contains one `key = value` pair per line, lines starting with `#` are ignored.
+    /// The keys are the field names of the descriptor, addresses and masks can be given in decimal or
+    /// as hex with a `0x` prefix, masks have to select a single run of bits. `frequency` is one of `fid_divisor` or `fid_5mhz`,
+    /// `voltage` is one of `svi2`, `svi3` or `unknown`. Fields that aren't listed keep their builtin value.
+    ///
+    /// ```text
+    /// # hypothetical family with moved energy counters
+    /// core_energy = 0xC00102A0
+    /// package_energy = 0xC00102A1
+    /// voltage = unknown
+    /// ```
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::current().with_overrides(&read_to_string(path)?)
+    }
+
+    /// Apply overrides in the format described in [`load`](FamilyDescriptor::load)
+    pub fn with_overrides(mut self, overrides: &str) -> Result<Self, Error> {
+        for (index, line) in overrides.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || Error::InvalidDescriptor {
+                line: index + 1,
+                content: line.to_string(),
+            };
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim();
+            let number = || match value.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16).map_err(|_| invalid()),
+                None => value.parse().map_err(|_| invalid()),
+            };
+            let mask = || {
+                number().and_then(|mask| is_contiguous(mask).then_some(mask).ok_or_else(invalid))
+            };
+            match key.trim() {
+                "power_unit" => self.power_unit = number()?,
+                "core_energy" => self.core_energy = number()?,
+                "package_energy" => self.package_energy = number()?,
+                "time_unit_mask" => self.time_unit_mask = mask()?,
+                "energy_unit_mask" => self.energy_unit_mask = mask()?,
+                "power_unit_mask" => self.power_unit_mask = mask()?,
+                "frequency" => {
+                    self.frequency = match value {
+                        "fid_divisor" => FrequencyEncoding::FidDivisor,
+                        "fid_5mhz" => FrequencyEncoding::Fid5Mhz,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "voltage" => {
+                    self.voltage = match value {
+                        "svi2" => VoltageEncoding::Svi2,
+                        "svi3" => VoltageEncoding::Svi3,
+                        "unknown" => VoltageEncoding::Unknown,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(self)
+    }
+
+    /// Decode the frequency in MHz from a P-state definition or the hardware P-state status
+    pub(crate) fn decode_frequency(&self, value: u64) -> Option<f64> {
+        match self.frequency {
+            FrequencyEncoding::Fid5Mhz => Some((value & 0xFFF) as f64 * 5.0),
+            FrequencyEncoding::FidDivisor => {
+                let fid = value & 0xFF;
+                let dfs = (value >> 8) & 0x3F;
+                (dfs > 0).then(|| fid as f64 * 200.0 / dfs as f64)
+            }
+        }
+    }
+
+    /// Decode the voltage from a P-state definition or the hardware P-state status
+    pub(crate) fn decode_voltage(&self, value: u64) -> Option<f64> {
+        let vid = ((value >> 14) & 0xFF) as f64;
+        match self.voltage {
+            VoltageEncoding::Svi2 => Some((1.55 - vid * 0.00625).max(0.0)),
+            VoltageEncoding::Svi3 => Some(0.245 + vid * 0.005),
+            VoltageEncoding::Unknown => None,
+        }
+    }
+}
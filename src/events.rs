@@ -0,0 +1,64 @@
+//! Bounded log of noteworthy events while reading the counters
+//!
+//! Events are kept in memory so tools can show what happened without integrating with a logging framework,
+//! once the log is full the oldest events are dropped.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// The number of events kept, older events are dropped
+const EVENT_LOG_SIZE: usize = 256;
+
+/// Something noteworthy that happened while reading the counters
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// The counter source picked when opening, `msr`, `backend` or the name of the sysfs driver
+    SourceSelected { source: &'static str },
+    /// Reading the msr of a cpu was blocked and the ioctl interface is used instead
+    IoctlFallback { cpu: u32 },
+    /// An energy domain of a cpu failed to read and won't be read again
+    DomainUnavailable { cpu: u32, domain: &'static str },
+    /// A monitored cpu went offline, it is skipped until it comes back online
+    CpuOffline { cpu: u32 },
+    /// A cpu that was offline came back online
+    CpuOnline { cpu: u32 },
+    /// A cpu that came online later was added by a rescan
+    CpuAdded { cpu: u32 },
+    /// Taking a snapshot failed
+    ReadError { message: String },
+    /// Energy counters wrapped around between two snapshots and were corrected
+    Wraparound { count: u32 },
+    /// The system was suspended between two snapshots
+    SuspendGap { duration: Duration },
+}
+
+/// An event and when it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggedEvent {
+    pub time: SystemTime,
+    pub event: Event,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct EventLog {
+    events: Mutex<VecDeque<LoggedEvent>>,
+}
+
+impl EventLog {
+    pub fn push(&self, event: Event) {
+        let mut events = self.events.lock().unwrap();
+        if events.len() == EVENT_LOG_SIZE {
+            events.pop_front();
+        }
+        events.push_back(LoggedEvent {
+            time: SystemTime::now(),
+            event,
+        });
+    }
+
+    pub fn events(&self) -> Vec<LoggedEvent> {
+        self.events.lock().unwrap().iter().cloned().collect()
+    }
+}
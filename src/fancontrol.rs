@@ -0,0 +1,112 @@
+//! Combined power, temperature and power limit events for fan control daemons
+
+use crate::powercap::package_zones;
+use crate::thermal::ThermalSensors;
+use crate::{package_values, CpuInfo, CpuPower, Error};
+use std::ops::ControlFlow;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// A package is considered limited when its power is within this fraction of its limit
+const LIMITED_THRESHOLD: f64 = 0.98;
+
+/// Power limit state of a single package
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct LimitState {
+    pub package: u32,
+    /// The long term power limit in watt
+    pub power_limit: f64,
+    /// Whether the package power is currently at the power limit
+    pub limited: bool,
+}
+
+/// A single sample for fan control
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct FanControlEvent {
+    pub power: CpuPower,
+    /// The control temperature (Tctl) in degrees celsius, if k10temp is available
+    pub temperature: Option<f64>,
+    /// The power limit state for every package that exposes its limits through powercap
+    pub limits: Vec<LimitState>,
+}
+
+/// Source of [`FanControlEvent`]s
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::{CpuInfo, Error};
+/// # use ryzen_reader::fancontrol::FanControlSource;
+/// # use std::ops::ControlFlow;
+/// # use std::time::Duration;
+/// #
+/// # fn main() -> Result<(), Error> {
+///     let source = FanControlSource::new(CpuInfo::new()?);
+///     source.run(Duration::from_secs(1), |event| {
+///         println!("{:?}C {:.2}W", event.temperature, event.power.packages().sum::<f64>());
+///         ControlFlow::Continue(())
+///     })?;
+/// #     Ok(())
+/// # }
+///```
+pub struct FanControlSource {
+    cpu: CpuInfo,
+    sensors: Option<ThermalSensors>,
+}
+
+impl FanControlSource {
+    pub fn new(cpu: CpuInfo) -> Self {
+        FanControlSource {
+            cpu,
+            sensors: ThermalSensors::find(),
+        }
+    }
+
+    /// Take a single sample
+    ///
+    /// Note that this method will block for the duration of [`CpuInfo::read`]
+    pub fn event(&self) -> Result<FanControlEvent, Error> {
+        let power = self.cpu.read()?;
+        let temperature = self.sensors.as_ref().and_then(ThermalSensors::tctl);
+
+        let mut limits = Vec::new();
+        for zone in package_zones()? {
+            let constraint = zone
+                .constraints()?
+                .into_iter()
+                .find(|constraint| constraint.name == "long_term");
+            if let Some(constraint) = constraint {
+                let current = package_values(&power.cores, power.package_aggregation)
+                    .find(|(package, _)| *package == zone.package())
+                    .map_or(0.0, |(_, power)| power);
+                limits.push(LimitState {
+                    package: zone.package(),
+                    power_limit: constraint.power_limit,
+                    limited: current >= constraint.power_limit * LIMITED_THRESHOLD,
+                });
+            }
+        }
+
+        Ok(FanControlEvent {
+            power,
+            temperature,
+            limits,
+        })
+    }
+
+    /// Take a sample every `interval` and pass it to `callback` until the callback breaks or an error occurs
+    pub fn run<F>(&self, interval: Duration, mut callback: F) -> Result<(), Error>
+    where
+        F: FnMut(&FanControlEvent) -> ControlFlow<()>,
+    {
+        loop {
+            let event = self.event()?;
+            if callback(&event).is_break() {
+                return Ok(());
+            }
+            sleep(interval);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Power versus frequency measurements using the cpufreq transition statistics
+
+use crate::sysfs::{read_string, read_value};
+use crate::{CpuInfo, Error};
+use std::collections::BTreeMap;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Energy and time a core spent at a single frequency
+#[derive(Debug, Clone, Copy)]
+pub struct FrequencyBucket {
+    /// The frequency in kHz
+    pub frequency: u32,
+    /// Energy used at this frequency in joule
+    pub energy: f64,
+    /// Time spent at this frequency
+    pub time: Duration,
+}
+
+impl FrequencyBucket {
+    /// Average power at this frequency in watt
+    pub fn power(&self) -> f64 {
+        self.energy / self.time.as_secs_f64()
+    }
+}
+
+/// Energy of a single core, bucketed by frequency
+#[derive(Debug, Clone)]
+pub struct CoreFrequencyBuckets {
+    pub cpu: u32,
+    /// Buckets in ascending frequency order
+    pub buckets: Vec<FrequencyBucket>,
+}
+
+/// Time spent in every frequency state in 10ms units, as reported by cpufreq stats
+fn time_in_state(cpu: u32) -> Option<BTreeMap<u32, u64>> {
+    let stats = read_string(format!(
+        "/sys/devices/system/cpu/cpu{}/cpufreq/stats/time_in_state",
+        cpu
+    ))
+    .ok()?;
+    stats
+        .lines()
+        .map(|line| {
+            let (frequency, time) = line.split_once(' ')?;
+            Some((frequency.parse().ok()?, time.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn current_frequency(cpu: u32) -> Option<u32> {
+    read_value(format!(
+        "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_cur_freq",
+        cpu
+    ))
+    .ok()
+}
+
+impl CpuInfo {
+    /// Measure the energy of every core for `duration`, bucketed by the frequency the core was running at
+    ///
+    /// Every `step` the energy counters and the cpufreq transition statistics are read, the energy used
+    /// during the step is split over the frequency states the core spent time in during that step.
+    /// When the step is shorter than the 10ms resolution of the statistics, the energy is attributed to
+    /// the current scaling frequency instead.
+    /// Cores without cpufreq statistics are left out.
+    ///
+    /// Note that this method will block for `duration`
+    pub fn frequency_buckets(
+        &self,
+        duration: Duration,
+        step: Duration,
+    ) -> Result<Vec<CoreFrequencyBuckets>, Error> {
+        let cpus: Vec<u32> = self.cores.iter().map(|core| core.cpu).collect();
+        let mut buckets: Vec<BTreeMap<u32, (f64, Duration)>> = vec![BTreeMap::new(); cpus.len()];
+
+        let mut last_states: Vec<_> = cpus.iter().map(|cpu| time_in_state(*cpu)).collect();
+        let mut last_snapshot = self.snapshot()?;
+
+        while last_snapshot.time.elapsed() < duration {
+            sleep(step);
+            let snapshot = self.snapshot()?;
+            let elapsed = snapshot.time.duration_since(last_snapshot.time);
+            let (energy, _) = last_snapshot.energy_until(&snapshot);
+
+            for (index, cpu) in cpus.iter().enumerate() {
+                let states = time_in_state(*cpu);
+                let core_energy = match (energy[index].core_power, &last_states[index], &states) {
+                    (Some(energy), Some(_), Some(_)) => energy,
+                    _ => continue,
+                };
+                let (last, current) = (last_states[index].as_ref(), states.as_ref());
+                let deltas: Vec<(u32, u64)> = current
+                    .into_iter()
+                    .flatten()
+                    .map(|(frequency, time)| {
+                        let last = last.and_then(|last| last.get(frequency)).copied();
+                        (*frequency, time.saturating_sub(last.unwrap_or(*time)))
+                    })
+                    .filter(|(_, delta)| *delta > 0)
+                    .collect();
+                let total: u64 = deltas.iter().map(|(_, delta)| delta).sum();
+
+                if total == 0 {
+                    if let Some(frequency) = current_frequency(*cpu) {
+                        let bucket = buckets[index].entry(frequency).or_default();
+                        bucket.0 += core_energy;
+                        bucket.1 += elapsed;
+                    }
+                } else {
+                    for (frequency, delta) in deltas {
+                        let share = delta as f64 / total as f64;
+                        let bucket = buckets[index].entry(frequency).or_default();
+                        bucket.0 += core_energy * share;
+                        bucket.1 += elapsed.mul_f64(share);
+                    }
+                }
+                last_states[index] = states;
+            }
+            last_snapshot = snapshot;
+        }
+
+        Ok(cpus
+            .into_iter()
+            .zip(buckets)
+            .filter(|(_, buckets)| !buckets.is_empty())
+            .map(|(cpu, buckets)| CoreFrequencyBuckets {
+                cpu,
+                buckets: buckets
+                    .into_iter()
+                    .map(|(frequency, (energy, time))| FrequencyBucket {
+                        frequency,
+                        energy,
+                        time,
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+}
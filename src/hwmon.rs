@@ -0,0 +1,300 @@
+//! Energy counters exposed through sysfs by hwmon drivers and powercap
+//!
+//! On Intel cpus the RAPL package and core (pp0) energy counters of the powercap interface are used.
+//! The `amd_energy` driver and its out of tree fork `zenergy` expose the same core and package energy counters
+//! as the msrs, accumulated to 64 bit and in microjoule, without requiring root or the msr module.
+//! The out of tree `zenpower` driver only exposes the power reported by the voltage regulators,
+//! which is integrated into a package energy counter.
+
+use crate::powercap::package_zones;
+use crate::sysfs::{read_string, read_value};
+use crate::{MsrValue, ENERGY_COUNTER_MASK};
+use std::collections::HashMap;
+use std::fs::{read_dir, read_link, read_to_string};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The energy unit counters from hwmon are converted to, the usual unit of the energy msrs
+const ENERGY_UNIT_BITS: u64 = 16;
+
+/// A power unit msr value with the usual time and power units and [`ENERGY_UNIT_BITS`] as energy unit
+const POWER_UNIT: u64 = 0x000A_0003 | ENERGY_UNIT_BITS << 8;
+
+/// The zenpower inputs that together make up the package power
+const ZENPOWER_PACKAGE_LABELS: [&str; 2] = ["SVI2_P_Core", "SVI2_P_SoC"];
+
+/// A hwmon driver providing energy or power for the cpus
+#[derive(Debug, Clone)]
+pub(crate) enum HwmonDevice {
+    /// `amd_energy` or `zenergy` energy inputs by label
+    Energy(HashMap<String, PathBuf>),
+    /// `zenpower` package power inputs, for every package
+    Power(Vec<Vec<PathBuf>>),
+    /// Intel RAPL powercap zones by package, the core zone is only handed out to the first cpu of the package
+    Rapl(HashMap<u32, RaplZones>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct RaplZones {
+    package: (PathBuf, u64),
+    core: Option<(PathBuf, u64)>,
+}
+
+/// The `energy_uj` file of a powercap zone and the value it wraps at
+fn rapl_energy(zone: &Path) -> Option<(PathBuf, u64)> {
+    let range = read_value(zone.join("max_energy_range_uj")).ok()?;
+    Some((zone.join("energy_uj"), range))
+}
+
+/// The cpu vendor from `/proc/cpuinfo`, `None` if it isn't reported
+pub(crate) fn vendor() -> Option<String> {
+    let cpuinfo = read_to_string("/proc/cpuinfo").ok()?;
+    let line = cpuinfo.lines().find(|line| line.starts_with("vendor_id"))?;
+    Some(line.split(':').nth(1)?.trim().to_string())
+}
+
+fn is_intel() -> bool {
+    vendor().is_some_and(|vendor| vendor == "GenuineIntel")
+}
+
+/// Whether the energy msrs of the cpu vendor can be read, only AMD and the AMD based Hygon cpus have them
+///
+/// Cpus that don't report a vendor are assumed to be supported.
+pub(crate) fn msr_vendor_supported() -> Result<(), String> {
+    match vendor() {
+        Some(vendor) if vendor != "AuthenticAMD" && vendor != "HygonGenuine" => Err(vendor),
+        _ => Ok(()),
+    }
+}
+
+fn find_rapl() -> Option<HwmonDevice> {
+    let zones: HashMap<u32, RaplZones> = package_zones()
+        .ok()?
+        .into_iter()
+        .filter_map(|zone| {
+            let package = rapl_energy(zone.path())?;
+            let core = read_dir(zone.path())
+                .ok()?
+                .flatten()
+                .map(|entry| entry.path())
+                .find(|path| read_string(path.join("name")).is_ok_and(|name| name == "core"))
+                .and_then(|path| rapl_energy(&path));
+            Some((zone.package(), RaplZones { package, core }))
+        })
+        .collect();
+    (!zones.is_empty()).then_some(HwmonDevice::Rapl(zones))
+}
+
+/// The order of a hwmon device, by the pci address of the device it belongs to and by the hwmon number
+///
+/// Drivers registering a device per package bind to the northbridge of every node, whose pci address follows the node id.
+/// Hwmon numbers are assigned in probe order and have to be compared numerically, `hwmon10` comes after `hwmon2`.
+fn device_order(path: &Path) -> (Option<String>, u32) {
+    let device = read_link(path.join("device"))
+        .ok()
+        .and_then(|device| Some(device.file_name()?.to_str()?.to_string()));
+    let index = path
+        .file_name()
+        .and_then(|name| name.to_str()?.strip_prefix("hwmon")?.parse().ok())
+        .unwrap_or(u32::MAX);
+    (device, index)
+}
+
+/// All hwmon devices with a driver name, ordered by package
+pub(crate) fn hwmon_devices(name: &str) -> Vec<PathBuf> {
+    let mut devices: Vec<PathBuf> = read_dir("/sys/class/hwmon")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| read_string(path.join("name")).is_ok_and(|found| found == name))
+        .collect();
+    devices.sort_by_cached_key(|path| device_order(path));
+    devices
+}
+
+/// All `<prefix>N_input` files of a hwmon device by their label
+pub(crate) fn labelled_inputs(device: &Path, prefix: &str) -> HashMap<String, PathBuf> {
+    read_dir(device)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let index = name.strip_prefix(prefix)?.strip_suffix("_label")?;
+            let label = read_string(entry.path()).ok()?;
+            Some((label, device.join(format!("{}{}_input", prefix, index))))
+        })
+        .collect()
+}
+
+/// Find a sysfs source for the cpu energy, preferring sources with energy counters
+pub(crate) fn find() -> Option<HwmonDevice> {
+    if is_intel() {
+        return find_rapl();
+    }
+
+    for name in ["amd_energy", "zenergy"] {
+        if let Some(device) = hwmon_devices(name).first() {
+            return Some(HwmonDevice::Energy(labelled_inputs(device, "energy")));
+        }
+    }
+
+    // zenpower registers a device for every package
+    let packages: Vec<Vec<PathBuf>> = hwmon_devices("zenpower")
+        .iter()
+        .map(|device| {
+            let mut inputs = labelled_inputs(device, "power");
+            ZENPOWER_PACKAGE_LABELS
+                .iter()
+                .filter_map(|label| inputs.remove(*label))
+                .collect()
+        })
+        .collect();
+    (!packages.is_empty()).then_some(HwmonDevice::Power(packages))
+}
+
+impl HwmonDevice {
+    /// A short description of the source for diagnostics
+    pub fn name(&self) -> &'static str {
+        match self {
+            HwmonDevice::Energy(_) => "hwmon energy",
+            HwmonDevice::Power(_) => "zenpower",
+            HwmonDevice::Rapl(_) => "powercap",
+        }
+    }
+
+    /// The counters for a logical cpu
+    ///
+    /// The energy drivers expose one core counter per physical core, read through the first sibling of the core,
+    /// and one counter per socket.
+    /// RAPL only has a single core counter per package which is assigned to the first cpu that requests it.
+    pub fn counters(&mut self, physical: u32, package: u32) -> HwmonCounters {
+        match self {
+            HwmonDevice::Energy(energy) => HwmonCounters {
+                core: energy
+                    .get(&format!("Ecore{:03}", physical))
+                    .cloned()
+                    .map(|path| HwmonInput::energy(path, None)),
+                package: energy
+                    .get(&format!("Esocket{}", package))
+                    .cloned()
+                    .map(|path| HwmonInput::energy(path, None)),
+            },
+            HwmonDevice::Rapl(zones) => match zones.get_mut(&package) {
+                Some(zones) => HwmonCounters {
+                    core: zones
+                        .core
+                        .take()
+                        .map(|(path, range)| HwmonInput::energy(path, Some(range))),
+                    package: Some(HwmonInput::energy(
+                        zones.package.0.clone(),
+                        Some(zones.package.1),
+                    )),
+                },
+                None => HwmonCounters {
+                    core: None,
+                    package: None,
+                },
+            },
+            HwmonDevice::Power(packages) => HwmonCounters {
+                core: None,
+                package: packages
+                    .get(package as usize)
+                    .filter(|inputs| !inputs.is_empty())
+                    .map(|inputs| HwmonInput::Power {
+                        inputs: inputs.clone(),
+                        integrated: Mutex::new(None),
+                    }),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+enum HwmonInput {
+    /// An energy counter in microjoule, optionally wrapping at `range`
+    Energy {
+        path: PathBuf,
+        range: Option<u64>,
+        /// The last value read and the sum of all ranges wrapped so far
+        wraps: Mutex<(u64, u64)>,
+    },
+    /// Power inputs in microwatt, integrated into an energy counter in microjoule on every read
+    Power {
+        inputs: Vec<PathBuf>,
+        integrated: Mutex<Option<(Instant, u64)>>,
+    },
+}
+
+impl HwmonInput {
+    fn energy(path: PathBuf, range: Option<u64>) -> Self {
+        HwmonInput::Energy {
+            path,
+            range,
+            wraps: Mutex::new((0, 0)),
+        }
+    }
+
+    fn read_microjoule(&self) -> io::Result<u64> {
+        match self {
+            HwmonInput::Energy { path, range, wraps } => {
+                let energy: u64 = read_value(path)?;
+                let mut wraps = wraps.lock().unwrap();
+                let (last, offset) = &mut *wraps;
+                // continue counting past the wrap so the counter converted to msr units stays continuous
+                if let Some(range) = range {
+                    if energy < *last {
+                        *offset += range;
+                    }
+                }
+                *last = energy;
+                Ok(energy + *offset)
+            }
+            HwmonInput::Power { inputs, integrated } => {
+                let mut microwatt = 0u64;
+                for input in inputs {
+                    microwatt += read_value::<u64, _>(input)?;
+                }
+                let now = Instant::now();
+                let mut integrated = integrated.lock().unwrap();
+                let energy = match *integrated {
+                    Some((last, energy)) => {
+                        energy + (microwatt as f64 * now.duration_since(last).as_secs_f64()) as u64
+                    }
+                    None => 0,
+                };
+                *integrated = Some((now, energy));
+                Ok(energy)
+            }
+        }
+    }
+}
+
+/// The hwmon inputs of a single cpu
+#[derive(Debug)]
+pub(crate) struct HwmonCounters {
+    core: Option<HwmonInput>,
+    package: Option<HwmonInput>,
+}
+
+impl HwmonCounters {
+    /// Read a counter in the format of the matching msr
+    pub fn read(&self, value: MsrValue) -> io::Result<u64> {
+        let input = match value {
+            MsrValue::PowerUnit => return Ok(POWER_UNIT),
+            MsrValue::CoreEnergy => &self.core,
+            MsrValue::PackageEnergy => &self.package,
+            _ => &None,
+        };
+        // report missing counters the same way as an unavailable msr, which marks the domain unavailable
+        let input = input
+            .as_ref()
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ENXIO))?;
+        let microjoule = input.read_microjoule()?;
+        let counter = (microjoule as u128 * (1 << ENERGY_UNIT_BITS) / 1_000_000) as u64;
+        Ok(counter & ENERGY_COUNTER_MASK)
+    }
+}
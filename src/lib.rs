@@ -1,24 +1,103 @@
-const AMD_TIME_UNIT_MASK: u64 = 0xF0000;
-const AMD_ENERGY_UNIT_MASK: u64 = 0x1F00;
-const AMD_POWER_UNIT_MASK: u64 = 0xF;
+/// `_IOWR('c', 0xA0, __u32[8])` from the msr driver
+#[cfg(target_os = "linux")]
+const X86_IOC_RDMSR_REGS: u64 = 0xC020_63A0;
+const ENERGY_COUNTER_MASK: u64 = 0xFFFF_FFFF;
+/// Upper bound for the power of a single energy domain, used to determine how long the counters can go without wrapping twice
+const MAX_DOMAIN_POWER: f64 = 1000.0;
+/// How long to watch the counters for when measuring the counter update period
+const UPDATE_MEASURE_TIME: Duration = Duration::from_millis(50);
 
-const MAX_CPUS: u32 = 1024;
+mod accumulated;
+mod affinity;
+#[cfg(feature = "tokio")]
+mod asynchronous;
+pub mod backend;
+pub mod benchmark;
+pub mod boost;
+pub mod budget;
+#[cfg(feature = "carbon")]
+pub mod carbon;
+pub mod clock;
+pub mod confidence;
+#[cfg(feature = "criterion")]
+pub mod criterion;
+pub mod descriptor;
+pub mod events;
+pub mod fancontrol;
+pub mod frequency;
+mod hwmon;
+pub mod load;
+pub mod minimal;
+pub mod monitor;
+pub mod powercap;
+pub mod process;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "uom")]
+mod quantities;
+#[cfg(feature = "tokio")]
+pub mod runtime;
+mod schema;
+pub mod scope;
+pub mod sensors;
+#[cfg(feature = "shared")]
+pub mod shared;
+#[cfg(feature = "smn")]
+pub mod smn;
+pub mod smu;
+pub mod support;
+pub mod sweep;
+mod sysfs;
+pub mod thermal;
+pub mod topology;
+#[cfg(feature = "shared")]
+pub mod tracker;
 
-use arrayvec::ArrayVec;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom};
+use accumulated::Accumulator;
+pub use accumulated::{AccumulatedEnergy, AverageEstimate};
+use backend::PowerBackend;
+use clock::{Clock, SystemClock};
+use descriptor::{FamilyDescriptor, VoltageEncoding};
+use events::{Event, EventLog, LoggedEvent};
+#[cfg(feature = "serde")]
+use schema::SchemaVersion;
+pub use schema::SCHEMA_VERSION;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{read_to_string, File, OpenOptions};
+use std::io::Read;
+#[cfg(target_os = "linux")]
 use std::mem::size_of;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::FileExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
 use std::str;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use topology::{AmdPstateInfo, CoreTopology, FrequencyInfo, Topology};
 
+/// The msrs read by the crate
+///
+/// The energy msrs are only the default addresses, the addresses used are taken from the [`FamilyDescriptor`] of the cpu.
 #[repr(u64)]
+#[derive(Debug, Clone, Copy)]
 enum MsrValue {
     PowerUnit = 0xC0010299,
     CoreEnergy = 0xC001029A,
     PackageEnergy = 0xC001029B,
+    /// IA32_MPERF, counts at the P0 frequency while the core is active
+    Mperf = 0xE7,
+    /// IA32_APERF, counts at the actual frequency while the core is active
+    Aperf = 0xE8,
+    PStateStatus = 0xC0010063,
+    PStateDef0 = 0xC0010064,
+    /// The frequency and voltage the core is currently running at
+    HwPStateStatus = 0xC0010293,
 }
 
 #[derive(Error, Debug)]
@@ -34,6 +113,58 @@ pub enum Error {
     NoCores,
     #[error("Invalid package data")]
     InvalidPackage,
+    #[error("Failed to signal process {0}: {1}")]
+    Signal(i32, #[source] std::io::Error),
+    #[error("Process {0} not found")]
+    ProcessNotFound(i32),
+    #[error("Failed to run command: {0}")]
+    Spawn(#[source] std::io::Error),
+    #[error("Interval of {interval:?} is longer than the maximum unambiguous interval of {max:?}")]
+    IntervalTooLong { interval: Duration, max: Duration },
+    #[error("IO error when trying to access powercap zone: {0}")]
+    Powercap(#[source] std::io::Error),
+    #[error("No powercap zone found for package {0}")]
+    NoPowercapZone(u32),
+    #[error("Invalid power limit of {0}W, the limit has to be a positive number")]
+    InvalidPowerLimit(f64),
+    #[error("Power limit of {watt}W is above the maximum of {max}W")]
+    PowerLimitTooHigh { watt: f64, max: f64 },
+    #[error("Monitoring requires {needed} file descriptors but the budget is {budget}")]
+    FdBudgetExceeded { needed: usize, budget: usize },
+    #[error("No time passed between the two snapshots")]
+    EmptyInterval,
+    #[error("Minimum interval of {min:?} is longer than the maximum interval of {max:?}")]
+    InvalidIntervalBounds { min: Duration, max: Duration },
+    #[error("Package energy is not available on cpu {0}")]
+    PackageEnergyUnavailable(u32),
+    #[error("Reading the cpu power is only supported on linux")]
+    UnsupportedPlatform,
+    #[error("Cpu family {family:#x} model {model:#x} is not supported")]
+    UnsupportedCpu { family: u32, model: u32 },
+    #[error("IO error when trying to access the smn interface: {0}")]
+    Smn(#[source] std::io::Error),
+    #[error("IO error when trying to read the smu telemetry: {0}")]
+    Smu(#[source] std::io::Error),
+    #[error("Unsupported smu telemetry table version {0:#x}")]
+    UnsupportedPmTable(u32),
+    #[error("The request ticket was not created by this tracker or has already ended")]
+    UnknownTicket,
+    #[error("Invalid line {line} in family descriptor: {content}")]
+    InvalidDescriptor { line: usize, content: String },
+    #[error("The requested counter source is not available")]
+    SourceUnavailable,
+    #[error(
+        "Invalid number of sub-intervals {subsamples}, the interval allows between 1 and {max}"
+    )]
+    InvalidSubsamples { subsamples: u32, max: u32 },
+    #[error("Invalid load pattern: {0}")]
+    InvalidLoadPattern(&'static str),
+    #[error("Invalid sweep: {0}")]
+    InvalidSweep(&'static str),
+    #[error(
+        "Cpu vendor {0} can't be read through the msrs and no sysfs energy counters are available"
+    )]
+    UnsupportedVendor(String),
 }
 
 impl From<std::io::Error> for Error {
@@ -46,80 +177,889 @@ impl From<std::io::Error> for Error {
     }
 }
 
+/// An energy counter source a [`CpuInfo`] can be opened with
+///
+/// By default the sysfs drivers are used when available and the msrs otherwise, see [`CpuInfo`] for the details.
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// The energy msrs through `/dev/cpu/*/msr`
+    Msr,
+    /// The hwmon drivers or the RAPL powercap zones
+    Sysfs,
+    /// A custom backend
+    Backend(Arc<dyn PowerBackend>),
+}
+
+/// Where the energy counters of a core are read from
+enum CounterSource {
+    Msr(File),
+    Hwmon(hwmon::HwmonCounters),
+    Backend(Arc<dyn PowerBackend>),
+}
+
 struct Core {
-    handle: Mutex<File>,
+    source: CounterSource,
+    cpu: u32,
     package: u32,
+    /// The lowest logical cpu id of the physical core
+    physical: u32,
+    /// The core complex die of the core, if known
+    ccd: Option<u32>,
+    /// Whether the package counter is read through this core
+    reads_package: bool,
+    /// The P0 frequency in MHz, only known when the frequency counters are read
+    base_frequency: Option<f64>,
+    descriptor: FamilyDescriptor,
+    last_raw: Mutex<RawMsrValues>,
+    use_ioctl: AtomicBool,
+    /// Cleared when the cpu was found to be offline, offline cpus are not read
+    online: AtomicBool,
+    core_energy_available: AtomicBool,
+    package_energy_available: AtomicBool,
+}
+
+/// The raw msr values last read from a cpu
+///
+/// Values are `None` if the register hasn't been read from the cpu yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RawMsrValues {
+    pub cpu: u32,
+    pub power_unit: Option<u64>,
+    pub core_energy: Option<u64>,
+    pub package_energy: Option<u64>,
+}
+
+impl fmt::Display for RawMsrValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn hex(f: &mut fmt::Formatter<'_>, name: &str, value: Option<u64>) -> fmt::Result {
+            match value {
+                Some(value) => write!(f, " {}=0x{:016x}", name, value),
+                None => write!(f, " {}=-", name),
+            }
+        }
+
+        write!(f, "cpu{}:", self.cpu)?;
+        hex(f, "power_unit", self.power_unit)?;
+        hex(f, "core_energy", self.core_energy)?;
+        hex(f, "package_energy", self.package_energy)
+    }
 }
 
 impl Core {
-    pub fn open(cpu_id: u32) -> Result<Self, Error> {
+    pub fn read_package(cpu_id: u32) -> Result<u32, Error> {
         let mut data = [0; 4];
-        let mut package_handle = OpenOptions::new().read(true).open(&format!(
+        let mut package_handle = OpenOptions::new().read(true).open(format!(
             "/sys/devices/system/cpu/cpu{}/topology/physical_package_id",
             cpu_id
         ))?;
-        package_handle.read(&mut data)?;
-        let package: u32 = str::from_utf8(&data)
+        // sysfs returns the whole id in one read, the unused part of the buffer stays zero and is trimmed below
+        let _ = package_handle.read(&mut data)?;
+        str::from_utf8(&data)
             .map_err(|_| Error::InvalidPackage)?
             .trim_end_matches('\u{0}')
             .trim()
             .parse()
-            .map_err(|_| Error::InvalidPackage)?;
+            .map_err(|_| Error::InvalidPackage)
+    }
 
-        let handle = OpenOptions::new()
+    fn open_msr(cpu_id: u32) -> std::io::Result<File> {
+        OpenOptions::new()
             .read(true)
             .write(false)
-            .open(&format!("/dev/cpu/{}/msr", cpu_id))?;
+            .open(format!("/dev/cpu/{}/msr", cpu_id))
+    }
 
-        Ok(Core {
-            handle: Mutex::new(handle),
+    pub fn open(cpu_id: u32, package: u32) -> Result<Self, Error> {
+        if cfg!(not(target_os = "linux")) {
+            return Err(Error::UnsupportedPlatform);
+        }
+        let handle = Self::open_msr(cpu_id)?;
+        Ok(Self::with_source(
+            CounterSource::Msr(handle),
+            cpu_id,
             package,
-        })
+        ))
     }
 
-    pub fn read(&self, value: MsrValue) -> Result<u64, Error> {
-        let mut handle = self.handle.lock().unwrap();
-        handle.seek(SeekFrom::Start(value as u64))?;
+    fn with_source(source: CounterSource, cpu_id: u32, package: u32) -> Self {
+        Core {
+            source,
+            cpu: cpu_id,
+            package,
+            physical: cpu_id,
+            ccd: None,
+            reads_package: true,
+            base_frequency: None,
+            descriptor: FamilyDescriptor::default(),
+            last_raw: Mutex::new(RawMsrValues {
+                cpu: cpu_id,
+                ..RawMsrValues::default()
+            }),
+            use_ioctl: AtomicBool::new(false),
+            online: AtomicBool::new(true),
+            core_energy_available: AtomicBool::new(true),
+            package_energy_available: AtomicBool::new(true),
+        }
+    }
+
+    pub fn reopen(&mut self) -> Result<(), Error> {
+        if let CounterSource::Msr(handle) = &mut self.source {
+            *handle = Self::open_msr(self.cpu)?;
+        }
+        Ok(())
+    }
 
+    #[cfg(target_os = "linux")]
+    fn read_at(handle: &File, register: u64) -> std::io::Result<u64> {
         let mut data = [0; size_of::<u64>()];
-        handle.read(&mut data)?;
+        // the msr driver only returns whole registers, anything shorter would be decoded with the upper bytes zeroed
+        // and show up as a bogus counter jump, so a short read has to be an error instead
+        handle.read_exact_at(&mut data, register)?;
         Ok(u64::from_le_bytes(data))
     }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read_at(_handle: &File, _register: u64) -> std::io::Result<u64> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn read_ioctl(handle: &File, register: u64) -> std::io::Result<u64> {
+        // eax, ecx, edx, ebx, esp, ebp, esi, edi
+        let mut registers = [0u32; 8];
+        registers[1] = register as u32;
+        let result = unsafe {
+            libc::ioctl(
+                handle.as_raw_fd(),
+                X86_IOC_RDMSR_REGS as _,
+                registers.as_mut_ptr(),
+            )
+        };
+        if result < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok((registers[2] as u64) << 32 | registers[0] as u64)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read_ioctl(_handle: &File, _register: u64) -> std::io::Result<u64> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    fn domain_available(&self, value: MsrValue) -> &AtomicBool {
+        match value {
+            MsrValue::CoreEnergy => &self.core_energy_available,
+            _ => &self.package_energy_available,
+        }
+    }
+
+    /// Read an energy counter, returns `None` if the energy domain is not available on this core
+    ///
+    /// Some platforms return EIO for the energy msrs on some cores while others work,
+    /// once a read fails that way the domain is considered unavailable for the core and isn't read again.
+    /// Reading the msrs of an offline cpu fails the same way, in that case the core is marked offline instead.
+    pub fn read_domain(&self, value: MsrValue) -> Result<Option<u64>, Error> {
+        let available = self.domain_available(value);
+        if !available.load(Ordering::Relaxed) || !self.is_online() {
+            return Ok(None);
+        }
+        match self.read(value) {
+            Ok(raw) => Ok(Some(raw & ENERGY_COUNTER_MASK)),
+            Err(Error::IO(e))
+                if matches!(e.raw_os_error(), Some(libc::EIO) | Some(libc::ENXIO)) =>
+            {
+                if matches!(self.source, CounterSource::Msr(_)) && !topology::is_online(self.cpu) {
+                    self.online.store(false, Ordering::Relaxed);
+                } else {
+                    available.store(false, Ordering::Relaxed);
+                }
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read an energy counter, retrying transient failures according to `policy`
+    ///
+    /// Only attempts that read the counter are counted as syscalls, unavailable domains and offline cpus are skipped.
+    fn read_domain_retrying(
+        &self,
+        value: MsrValue,
+        policy: RetryPolicy,
+        stats: &mut ReadStats,
+    ) -> Result<Option<u64>, Error> {
+        let mut backoff = policy.backoff;
+        let mut attempt = 0;
+        loop {
+            if self.domain_available(value).load(Ordering::Relaxed) && self.is_online() {
+                stats.syscalls += 1;
+            }
+            match self.read_domain(value) {
+                Err(Error::IO(e)) if attempt < policy.retries && is_transient(&e) => {
+                    attempt += 1;
+                    stats.retries += 1;
+                    sleep(backoff);
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::Relaxed)
+    }
+
+    /// Update whether the cpu is online, returns `true` if it changed
+    ///
+    /// Only cpus read through the msrs are tracked, the other sources keep working for offline cpus.
+    fn set_online(&self, online: bool) -> bool {
+        matches!(self.source, CounterSource::Msr(_))
+            && self.online.swap(online, Ordering::Relaxed) != online
+    }
+
+    pub fn core_energy_available(&self) -> bool {
+        self.core_energy_available.load(Ordering::Relaxed)
+    }
+
+    pub fn package_energy_available(&self) -> bool {
+        self.package_energy_available.load(Ordering::Relaxed)
+    }
+
+    /// The address of a register on this core
+    fn address(&self, value: MsrValue) -> u64 {
+        match value {
+            MsrValue::PowerUnit => self.descriptor.power_unit,
+            MsrValue::CoreEnergy => self.descriptor.core_energy,
+            MsrValue::PackageEnergy => self.descriptor.package_energy,
+            _ => value as u64,
+        }
+    }
+
+    fn read_msr(&self, handle: &File, value: MsrValue) -> std::io::Result<u64> {
+        let address = self.address(value);
+        if self.use_ioctl.load(Ordering::Relaxed) {
+            Self::read_ioctl(handle, address)
+        } else {
+            match Self::read_at(handle, address) {
+                // some hardened kernels block reading the msr device but still allow the ioctl interface
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    let raw = Self::read_ioctl(handle, address).map_err(|_| e)?;
+                    self.use_ioctl.store(true, Ordering::Relaxed);
+                    Ok(raw)
+                }
+                result => result,
+            }
+        }
+    }
+
+    pub fn read(&self, value: MsrValue) -> Result<u64, Error> {
+        let raw = match &self.source {
+            CounterSource::Msr(handle) => self.read_msr(handle, value)?,
+            CounterSource::Hwmon(counters) => counters.read(value)?,
+            CounterSource::Backend(backend) => match value {
+                MsrValue::PowerUnit => backend.read_units(self.cpu)?,
+                MsrValue::CoreEnergy => backend.read_core_energy(self.cpu)?,
+                MsrValue::PackageEnergy => backend.read_package_energy(self.cpu)?,
+                _ => return Err(std::io::Error::from_raw_os_error(libc::ENXIO).into()),
+            },
+        };
+
+        let mut last_raw = self.last_raw.lock().unwrap();
+        match value {
+            MsrValue::PowerUnit => last_raw.power_unit = Some(raw),
+            MsrValue::CoreEnergy => last_raw.core_energy = Some(raw),
+            MsrValue::PackageEnergy => last_raw.package_energy = Some(raw),
+            _ => {}
+        }
+        Ok(raw)
+    }
+
+    /// Read a register that isn't available through every counter source, `None` if it can't be read
+    fn read_optional(&self, value: MsrValue) -> Result<Option<u64>, Error> {
+        match self.read(value) {
+            Ok(raw) => Ok(Some(raw)),
+            Err(Error::IO(e))
+                if matches!(e.raw_os_error(), Some(libc::EIO) | Some(libc::ENXIO)) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Decode the P0 frequency in MHz from the first P-state definition
+    fn read_base_frequency(&self) -> Result<Option<f64>, Error> {
+        Ok(match self.read_optional(MsrValue::PStateDef0)? {
+            // bit 63 marks the P-state as enabled
+            Some(definition) if definition >> 63 == 1 => {
+                self.descriptor.decode_frequency(definition)
+            }
+            _ => None,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct CorePower {
-    core_power: f64,
-    package_power: f64,
+    /// `None` if the core energy domain is not available on the core
+    core_power: Option<f64>,
+    /// `None` if the package energy domain is not available on the core
+    package_power: Option<f64>,
+    cpu: u32,
     package: u32,
+    /// The lowest logical cpu id of the physical core, shared by all SMT siblings
+    physical: u32,
+    ccd: Option<u32>,
+    /// Effective frequency in MHz, `None` if the frequency counters aren't read
+    frequency: Option<f64>,
+    /// Core voltage at the end of the reading, `None` if the voltage isn't read
+    voltage: Option<f64>,
+    /// The P-state at the end of the reading
+    pstate: Option<u32>,
+    /// Time between the reads of this core in the two snapshots
+    interval: Duration,
+}
+
+/// Power draw of a single cpu core and where it sits in the topology
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct CoreReading {
+    /// The logical cpu id
+    pub cpu: u32,
+    /// The lowest logical cpu id of the physical core, shared by all SMT siblings
+    pub physical: u32,
+    /// The physical package the cpu belongs to
+    pub package: u32,
+    /// The core complex die the cpu belongs to, `None` if it can't be determined
+    pub ccd: Option<u32>,
+    /// The power draw of the core in watt
+    pub power: f64,
+    /// The average effective frequency of the core while active in MHz,
+    /// only available with [`frequency_counters`](CpuInfoBuilder::frequency_counters)
+    pub frequency: Option<f64>,
+    /// The P-state of the core at the end of the reading,
+    /// only available with [`frequency_counters`](CpuInfoBuilder::frequency_counters)
+    pub pstate: Option<u32>,
+    /// The voltage requested by the core at the end of the reading in volt,
+    /// only available with [`core_voltage`](CpuInfoBuilder::core_voltage)
+    pub voltage: Option<f64>,
+}
+
+/// Combined power draw of the cores of a single core complex die
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct CcdPower {
+    pub package: u32,
+    pub ccd: u32,
+    /// The combined power of the physical cores of the ccd in watt
+    pub power: f64,
+    /// The number of physical cores with a reading
+    pub cores: u32,
+}
+
+impl CoreReading {
+    /// The estimated current draw of the core in ampere
+    pub fn current(&self) -> Option<f64> {
+        self.voltage
+            .filter(|voltage| *voltage > 0.0)
+            .map(|voltage| self.power / voltage)
+    }
+}
+
+/// The package value of every package that has the package energy available as `(package, value)`, ordered by package id
+fn package_values(
+    cores: &[CorePower],
+    aggregation: PackageAggregation,
+) -> impl Iterator<Item = (u32, f64)> {
+    package_readings(cores).map(move |(package, mut values)| {
+        let value = match aggregation {
+            PackageAggregation::First => values[0],
+            PackageAggregation::Average => values.iter().sum::<f64>() / values.len() as f64,
+            PackageAggregation::Median => {
+                values.sort_unstable_by(f64::total_cmp);
+                let middle = values.len() / 2;
+                if values.len() % 2 == 0 {
+                    (values[middle - 1] + values[middle]) / 2.0
+                } else {
+                    values[middle]
+                }
+            }
+        };
+        (package, value)
+    })
+}
+
+/// All package values read through the cores of every package, ordered by package id
+fn package_readings(cores: &[CorePower]) -> impl Iterator<Item = (u32, Vec<f64>)> {
+    let mut packages: BTreeMap<u32, Vec<f64>> = BTreeMap::new();
+    for core in cores {
+        if let Some(value) = core.package_power {
+            packages.entry(core.package).or_default().push(value);
+        }
+    }
+    packages.into_iter()
+}
+
+/// The cost of taking a reading
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct ReadStats {
+    /// The number of syscalls made to read the msrs
+    pub syscalls: u32,
+    /// The total time spent reading msrs
+    pub total_latency: Duration,
+    /// The longest time spent reading the msrs of a single core
+    pub per_core_latency_max: Duration,
+    /// The number of msr reads that were retried after a transient failure
+    pub retries: u32,
+}
+
+impl ReadStats {
+    fn merge(&self, other: &ReadStats) -> ReadStats {
+        ReadStats {
+            syscalls: self.syscalls + other.syscalls,
+            total_latency: self.total_latency + other.total_latency,
+            per_core_latency_max: self.per_core_latency_max.max(other.per_core_latency_max),
+            retries: self.retries + other.retries,
+        }
+    }
+}
+
+/// Metadata about how a reading was taken, to allow filtering low quality samples
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct SampleQuality {
+    /// The actual time between the two sets of reads the power was calculated from
+    pub interval: Duration,
+    /// The longest time between the first and last msr read within one set of reads
+    pub read_spread: Duration,
+    /// The number of energy counter wraparounds that were corrected
+    pub wraparounds: u32,
+    /// The number of cores that were skipped because they couldn't be read
+    pub skipped_cores: u32,
+    /// The largest difference between the time between the reads of a single core and `interval`
+    ///
+    /// Power is calculated over `interval` for all cores, a large skew biases the power of individual cores.
+    pub max_core_skew: Duration,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CpuPower {
-    cores: ArrayVec<[CorePower; 64]>,
+    cores: Vec<CorePower>,
+    package_aggregation: PackageAggregation,
+    quality: SampleQuality,
+    stats: ReadStats,
+    #[cfg_attr(feature = "serde", serde(default))]
+    sources: Vec<(String, CpuPower)>,
+    #[cfg(feature = "serde")]
+    #[serde(default)]
+    schema_version: SchemaVersion,
 }
 
 impl CpuPower {
+    /// The readings of the additional sources set with [`CpuInfoBuilder::also_read`], by source name
+    ///
+    /// Sources are in the order they were added, the name is the one reported by [`CpuInfo::source`].
+    pub fn sources(&self) -> impl Iterator<Item = (&str, &CpuPower)> + '_ {
+        self.sources
+            .iter()
+            .map(|(name, power)| (name.as_str(), power))
+    }
+
     /// Get an iterator for all cpu cores in the system and their power draw in watt
+    ///
+    /// Cores are ordered by package and then by cpu id.
+    /// Cores that don't have the core energy domain available are skipped.
     pub fn cores<'a>(&'a self) -> impl Iterator<Item = f64> + 'a {
-        self.cores.iter().map(|core| core.core_power)
+        self.cores.iter().filter_map(|core| core.core_power)
+    }
+
+    /// Get an iterator for all cpu cores in the system with their power draw and position in the topology
+    ///
+    /// Cores are reported in the same order as [`cores`](CpuPower::cores), including the skipping of unavailable cores.
+    pub fn core_readings(&self) -> impl Iterator<Item = CoreReading> + '_ {
+        self.cores.iter().filter_map(|core| {
+            Some(CoreReading {
+                cpu: core.cpu,
+                physical: core.physical,
+                package: core.package,
+                ccd: core.ccd,
+                power: core.core_power?,
+                frequency: core.frequency,
+                pstate: core.pstate,
+                voltage: core.voltage,
+            })
+        })
+    }
+
+    /// Get an iterator for all cpu cores in the system and their average effective frequency while active in MHz
+    ///
+    /// Cores are reported in the same order as [`cores`](CpuPower::cores), including the skipping of unavailable cores.
+    /// The frequency is `None` unless [`frequency_counters`](CpuInfoBuilder::frequency_counters) is enabled
+    /// and the core was active during the reading.
+    pub fn core_frequencies(&self) -> impl Iterator<Item = Option<f64>> + '_ {
+        self.cores
+            .iter()
+            .filter(|core| core.core_power.is_some())
+            .map(|core| core.frequency)
+    }
+
+    /// Get an iterator for all cpu cores in the system and their voltage at the end of the reading in volt
+    ///
+    /// Cores are reported in the same order as [`cores`](CpuPower::cores), including the skipping of unavailable cores.
+    /// The voltage is `None` unless [`core_voltage`](CpuInfoBuilder::core_voltage) is enabled.
+    pub fn core_voltages(&self) -> impl Iterator<Item = Option<f64>> + '_ {
+        self.cores
+            .iter()
+            .filter(|core| core.core_power.is_some())
+            .map(|core| core.voltage)
+    }
+
+    /// Get an iterator for all physical cpu cores in the system and their power draw in watt
+    ///
+    /// SMT siblings share the core energy counter, only the first sibling of every physical core is reported.
+    pub fn physical_cores(&self) -> impl Iterator<Item = f64> + '_ {
+        let mut seen = Vec::new();
+        self.cores.iter().filter_map(move |core| {
+            if seen.contains(&core.physical) {
+                return None;
+            }
+            let power = core.core_power?;
+            seen.push(core.physical);
+            Some(power)
+        })
+    }
+
+    /// Get the combined power of the physical cores of every ccd
+    ///
+    /// The ccds are taken from the L3 cache topology, on Zen 2 and earlier this groups the cores by core complex instead.
+    /// Cores without a known ccd or core power are left out, ccds are ordered by package and ccd.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let power = CpuInfo::new()?.read()?;
+    ///     for ccd in power.ccds() {
+    ///         println!("ccd {}: {:.2}W over {} cores", ccd.ccd, ccd.power, ccd.cores);
+    ///     }
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn ccds(&self) -> impl Iterator<Item = CcdPower> {
+        let mut ccds: BTreeMap<(u32, u32), CcdPower> = BTreeMap::new();
+        let mut seen = Vec::new();
+        for core in &self.cores {
+            let (ccd, power) = match (core.ccd, core.core_power) {
+                (Some(ccd), Some(power)) => (ccd, power),
+                _ => continue,
+            };
+            // SMT siblings share the core energy counter
+            if seen.contains(&core.physical) {
+                continue;
+            }
+            seen.push(core.physical);
+            let entry = ccds.entry((core.package, ccd)).or_insert_with(|| CcdPower {
+                package: core.package,
+                ccd,
+                power: 0.0,
+                cores: 0,
+            });
+            entry.power += power;
+            entry.cores += 1;
+        }
+        ccds.into_values()
     }
 
     /// Get an iterator for all cpu packages in the system and their power draw in watt
+    ///
+    /// Packages are ordered by package id.
     pub fn packages<'a>(&'a self) -> impl Iterator<Item = f64> + 'a {
-        let mut last_package = u32::max_value();
+        package_values(&self.cores, self.package_aggregation).map(|(_, power)| power)
+    }
+
+    /// Get an iterator for all cpu cores in the system and the energy they used over the sampling window in joule
+    ///
+    /// Cores that don't have the core energy domain available are skipped.
+    pub fn core_energy(&self) -> impl Iterator<Item = f64> + '_ {
+        let seconds = self.quality.interval.as_secs_f64();
+        self.cores().map(move |power| power * seconds)
+    }
+
+    /// Get an iterator for all cpu packages in the system and the energy they used over the sampling window in joule
+    pub fn package_energy(&self) -> impl Iterator<Item = f64> + '_ {
+        let seconds = self.quality.interval.as_secs_f64();
+        self.packages().map(move |power| power * seconds)
+    }
 
+    /// Get an iterator for all cpu packages in the system and the difference between the highest and lowest
+    /// package power read through its cores in watt
+    ///
+    /// The package counter is read through every core of a package, since the reads don't happen at the same time
+    /// the values differ slightly. A large divergence indicates that the reads are skewed.
+    pub fn package_divergence(&self) -> impl Iterator<Item = f64> {
+        package_readings(&self.cores).map(|(_, values)| {
+            let max = values.iter().copied().fold(f64::MIN, f64::max);
+            let min = values.iter().copied().fold(f64::MAX, f64::min);
+            max - min
+        })
+    }
+
+    /// Get an iterator for all cpu cores in the system and the time between their reads in the two snapshots
+    ///
+    /// Cores are reported in the same order as [`cores`](CpuPower::cores), including the skipping of unavailable cores.
+    pub fn core_intervals(&self) -> impl Iterator<Item = Duration> + '_ {
         self.cores
             .iter()
-            .filter(move |core| {
-                if core.package != last_package {
-                    last_package = core.package;
-                    true
-                } else {
-                    false
+            .filter(|core| core.core_power.is_some())
+            .map(|core| core.interval)
+    }
+
+    /// Get the metadata about how this reading was taken
+    pub fn quality(&self) -> &SampleQuality {
+        &self.quality
+    }
+
+    /// Get the cost of taking this reading
+    pub fn stats(&self) -> &ReadStats {
+        &self.stats
+    }
+
+    /// Calculate the power levels between two snapshots of the same [`CpuInfo`]
+    ///
+    /// The interval between the snapshots can be at most [`CpuInfo::max_interval`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, CpuPower, Error};
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let cpu = CpuInfo::new()?;
+    ///     let start = cpu.snapshot()?;
+    ///     // do other work
+    ///     let end = cpu.snapshot()?;
+    ///     let power = CpuPower::between(&start, &end)?;
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn between(start: &EnergySnapshot, end: &EnergySnapshot) -> Result<CpuPower, Error> {
+        let interval = end.time.saturating_duration_since(start.time);
+        if interval.is_zero() {
+            return Err(Error::EmptyInterval);
+        }
+        let max = max_interval(start.energy_unit);
+        if interval > max {
+            return Err(Error::IntervalTooLong { interval, max });
+        }
+        let seconds = interval.as_secs_f64();
+        let (mut cores, wraparounds) = start.energy_until(end);
+        let noise_policy = start.noise_policy;
+
+        let mut skipped_cores = 0;
+        let mut max_core_skew = Duration::ZERO;
+        for core in &mut cores {
+            if core.core_power.is_none() {
+                skipped_cores += 1;
+            }
+            max_core_skew = max_core_skew.max(core.interval.abs_diff(interval));
+            core.core_power = core
+                .core_power
+                .map(|energy| noise_policy.apply(energy / seconds));
+            core.package_power = core
+                .package_power
+                .map(|energy| noise_policy.apply(energy / seconds));
+        }
+
+        Ok(CpuPower {
+            cores,
+            package_aggregation: start.package_aggregation,
+            quality: SampleQuality {
+                interval,
+                read_spread: start.spread.max(end.spread),
+                wraparounds,
+                skipped_cores,
+                max_core_skew,
+            },
+            stats: start.stats.merge(&end.stats),
+            sources: start
+                .sources
+                .iter()
+                .zip(&end.sources)
+                .map(|((name, start), (_, end))| {
+                    Ok((name.to_string(), CpuPower::between(start, end)?))
+                })
+                .collect::<Result<_, Error>>()?,
+            #[cfg(feature = "serde")]
+            schema_version: SchemaVersion,
+        })
+    }
+}
+
+/// Raw energy counters of a single core
+#[derive(Debug, Clone, Copy)]
+struct RawCounters {
+    core_energy: Option<u64>,
+    package_energy: Option<u64>,
+    cpu: u32,
+    package: u32,
+    physical: u32,
+    ccd: Option<u32>,
+    aperf: Option<u64>,
+    mperf: Option<u64>,
+    pstate: Option<u32>,
+    base_frequency: Option<f64>,
+    voltage: Option<f64>,
+    /// Time the counters of this core were read
+    time: Instant,
+}
+
+/// The energy counters of all cores, read at one point in time
+///
+/// Taken with [`CpuInfo::snapshot`], two snapshots of the same [`CpuInfo`] can be turned into
+/// a power reading with [`CpuPower::between`].
+#[derive(Debug, Clone)]
+pub struct EnergySnapshot {
+    counters: Vec<RawCounters>,
+    /// Time of the first read
+    time: Instant,
+    /// Time between the first and last read
+    spread: Duration,
+    stats: ReadStats,
+    energy_unit: f64,
+    noise_policy: NoisePolicy,
+    package_aggregation: PackageAggregation,
+    /// Monotonic time and time since boot including suspend, to detect suspends between snapshots
+    suspend_clock: Option<(Instant, Duration)>,
+    /// Snapshots of the additional sources
+    sources: Vec<(&'static str, EnergySnapshot)>,
+}
+
+impl EnergySnapshot {
+    /// The time the snapshot was taken
+    pub fn time(&self) -> Instant {
+        self.time
+    }
+
+    /// The energy used by every core between two snapshots in joule and the number of corrected wraparounds
+    fn energy_until(&self, end: &EnergySnapshot) -> (Vec<CorePower>, u32) {
+        let energy_unit = self.energy_unit;
+        let mut wraparounds = 0;
+
+        // cores are matched by cpu id, the set of cores changes when cpus are added by a rescan
+        let cores = end
+            .counters
+            .iter()
+            .enumerate()
+            .map(|(index, end)| {
+                let start = find_cpu(&self.counters, index, end.cpu, |start| start.cpu);
+                let mut energy = |start: Option<u64>, end: Option<u64>| {
+                    let (energy, wrapped) = counter_delta(start?, end?);
+                    wraparounds += wrapped as u32;
+                    Some(energy as f64 * energy_unit)
+                };
+
+                CorePower {
+                    core_power: energy(start.and_then(|start| start.core_energy), end.core_energy),
+                    package_power: energy(
+                        start.and_then(|start| start.package_energy),
+                        end.package_energy,
+                    ),
+                    cpu: end.cpu,
+                    package: end.package,
+                    physical: end.physical,
+                    ccd: end.ccd,
+                    frequency: start.and_then(|start| effective_frequency(start, end)),
+                    pstate: end.pstate,
+                    voltage: end.voltage,
+                    interval: start.map_or(Duration::ZERO, |start| {
+                        end.time.saturating_duration_since(start.time)
+                    }),
                 }
             })
-            .map(|core| core.package_power)
+            .collect();
+
+        (cores, wraparounds)
+    }
+}
+
+/// Find the item for `cpu`, which is usually at the same `index`
+pub(crate) fn find_cpu<T>(
+    items: &[T],
+    index: usize,
+    cpu: u32,
+    key: impl Fn(&T) -> u32,
+) -> Option<&T> {
+    match items.get(index) {
+        Some(item) if key(item) == cpu => Some(item),
+        _ => items.iter().find(|item| key(item) == cpu),
+    }
+}
+
+/// The index of the first core of every package, the cores have to be ordered by package
+fn first_package_cores(cores: &[Core]) -> Vec<usize> {
+    let mut last_package = u32::MAX;
+    cores
+        .iter()
+        .enumerate()
+        .filter(|(_, core)| {
+            let first = core.package != last_package;
+            last_package = core.package;
+            first
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// The effective frequency in MHz between two readings of the frequency counters, `None` if the core was idle
+fn effective_frequency(start: &RawCounters, end: &RawCounters) -> Option<f64> {
+    // the frequency counters are 64 bit and don't wrap in practice
+    let aperf = end.aperf?.wrapping_sub(start.aperf?);
+    let mperf = end.mperf?.wrapping_sub(start.mperf?);
+    let base_frequency = end.base_frequency?;
+    (mperf > 0).then(|| base_frequency * aperf as f64 / mperf as f64)
+}
+
+/// Time since boot including time spent suspended
+#[cfg(target_os = "linux")]
+fn boot_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut time) } != 0 {
+        return None;
+    }
+    Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn boot_time() -> Option<Duration> {
+    None
+}
+
+/// The longest interval for which counters with the given energy unit can not wrap around more than once
+fn max_interval(energy_unit: f64) -> Duration {
+    let counter_range = (ENERGY_COUNTER_MASK + 1) as f64 * energy_unit;
+    Duration::from_secs_f64(counter_range / MAX_DOMAIN_POWER)
+}
+
+/// Difference between two readings of a 32 bit energy counter and whether the counter wrapped in between
+fn counter_delta(start: u64, end: u64) -> (u64, bool) {
+    if end >= start {
+        (end - start, false)
+    } else {
+        (end + ENERGY_COUNTER_MASK + 1 - start, true)
     }
 }
 
@@ -127,19 +1067,378 @@ impl CpuPower {
 struct PowerUnits {
     time_unit: f64,
     energy_unit: f64,
+    // decoded for completeness, no msr power values are read yet
+    #[allow(dead_code)]
     power_unit: f64,
 }
 
+/// Builder for a [`CpuInfo`] with non-default options
+#[derive(Debug, Clone, Default)]
+pub struct CpuInfoBuilder {
+    backend: Option<Arc<dyn PowerBackend>>,
+    /// Whether to require (`true`) or skip (`false`) the sysfs drivers, `None` to use them when available
+    sysfs: Option<bool>,
+    also_read: Vec<Source>,
+    clock: Option<Arc<dyn Clock>>,
+    topology_cache: Option<PathBuf>,
+    cpus: Option<Vec<u32>>,
+    noise_policy: NoisePolicy,
+    package_aggregation: PackageAggregation,
+    retry_policy: RetryPolicy,
+    unit_overrides: UnitOverrides,
+    frequency_counters: bool,
+    core_voltage: bool,
+    single_package_read: bool,
+    numa_local_reads: bool,
+    packages_only: bool,
+    fd_budget: Option<usize>,
+    descriptor: Option<FamilyDescriptor>,
+}
+
+/// By default at most half of the open file limit is used for msr handles
+#[cfg(unix)]
+fn default_fd_budget() -> usize {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0
+        || limit.rlim_cur == libc::RLIM_INFINITY
+    {
+        return usize::MAX;
+    }
+    (limit.rlim_cur / 2) as usize
+}
+
+#[cfg(not(unix))]
+fn default_fd_budget() -> usize {
+    usize::MAX
+}
+
+impl CpuInfoBuilder {
+    /// Cache the discovered cpu topology in a file
+    ///
+    /// The package, physical core and ccd of every cpu are cached. The cache is validated against the cpu model
+    /// and the set of online cpus and rebuilt when either changed, failing to write the cache is not considered an error.
+    pub fn topology_cache<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.topology_cache = Some(path.into());
+        self
+    }
+
+    /// Only monitor the given logical cpus
+    ///
+    /// Only the handles for these cpus are opened, cpus that don't exist or are offline are ignored.
+    /// The package power still covers the whole package of the monitored cpus.
+    pub fn cpus(mut self, cpus: &[u32]) -> Self {
+        self.cpus = Some(cpus.to_vec());
+        self
+    }
+
+    /// Read the energy counters from a custom backend instead of the msrs or sysfs
+    ///
+    /// The cpus to monitor are taken from the backend, the topology cache is not used.
+    pub fn backend<B: PowerBackend + 'static>(mut self, backend: B) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Read the energy counters from a specific source instead of picking one automatically
+    ///
+    /// Opening fails with [`Error::SourceUnavailable`] if [`Source::Sysfs`] is requested but no sysfs driver is available.
+    pub fn source(mut self, source: Source) -> Self {
+        self.backend = None;
+        self.sysfs = None;
+        match source {
+            Source::Msr => self.sysfs = Some(false),
+            Source::Sysfs => self.sysfs = Some(true),
+            Source::Backend(backend) => self.backend = Some(backend),
+        }
+        self
+    }
+
+    /// Use the source saved by an earlier [`CpuInfo::benchmark_backends`] run, see [`BackendBenchmark::save`](benchmark::BackendBenchmark::save)
+    ///
+    /// Replaces the source as with [`source`](CpuInfoBuilder::source), a missing or invalid file leaves the source untouched.
+    pub fn saved_source<P: AsRef<Path>>(self, path: P) -> Self {
+        match benchmark::load_source(path) {
+            Ok(Some(source)) => self.source(source),
+            _ => self,
+        }
+    }
+
+    /// Also read the energy counters from another source for every snapshot
+    ///
+    /// The readings of every additional source are reported next to the main reading by [`CpuPower::sources`],
+    /// allowing the sources to be cross validated permanently. The additional sources are opened with the same options,
+    /// a snapshot fails if any of the sources fails to read.
+    pub fn also_read(mut self, source: Source) -> Self {
+        self.also_read.push(source);
+        self
+    }
+
+    /// Use a custom clock for the snapshot timestamps the power is calculated with
+    ///
+    /// Note that the blocking read methods still sleep for the requested interval in real time.
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Set the policy for cleaning up noisy near-zero power values
+    pub fn noise_policy(mut self, policy: NoisePolicy) -> Self {
+        self.noise_policy = policy;
+        self
+    }
+
+    /// Set how transient msr read failures are retried
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Only read the package counter through the first core of every package
+    ///
+    /// This avoids the divergence between package values read through different cores and saves a syscall per core,
+    /// the [`PackageAggregation`] has no effect when enabled.
+    pub fn single_package_read(mut self, enabled: bool) -> Self {
+        self.single_package_read = enabled;
+        self
+    }
+
+    /// Set how the package value is derived when multiple cores of a package report the package counter
+    pub fn package_aggregation(mut self, aggregation: PackageAggregation) -> Self {
+        self.package_aggregation = aggregation;
+        self
+    }
+
+    /// Only open a single cpu per package
+    ///
+    /// This only needs one file descriptor per package instead of one per logical cpu,
+    /// but [`CpuPower::cores`] will only contain the core that was opened for each package.
+    pub fn packages_only(mut self, enabled: bool) -> Self {
+        self.packages_only = enabled;
+        self
+    }
+
+    /// Set the maximum number of file descriptors to use for msr handles
+    ///
+    /// One descriptor is kept open for every monitored cpu, if there are more cpus than the budget allows
+    /// only a single cpu per package is opened as with [`packages_only`](CpuInfoBuilder::packages_only),
+    /// if the budget doesn't even allow that [`Error::FdBudgetExceeded`] is returned.
+    /// Defaults to half of the soft `RLIMIT_NOFILE` limit.
+    pub fn fd_budget(mut self, budget: usize) -> Self {
+        self.fd_budget = Some(budget);
+        self
+    }
+
+    /// Read the msrs of every package from a thread running on that package
+    ///
+    /// On multi socket systems this avoids the cross socket latency for every read and tightens the time between
+    /// the reads of the cores within a snapshot, at the cost of moving the calling thread between packages.
+    /// The original affinity of the thread is restored after reading.
+    pub fn numa_local_reads(mut self, enabled: bool) -> Self {
+        self.numa_local_reads = enabled;
+        self
+    }
+
+    /// Also read the APERF/MPERF frequency counters and the P-state of every core
+    ///
+    /// This costs three extra msr reads per core for every snapshot,
+    /// the frequencies are reported through [`CpuPower::core_frequencies`] and [`CpuPower::core_readings`].
+    /// Only available when reading the msrs directly.
+    pub fn frequency_counters(mut self, enabled: bool) -> Self {
+        self.frequency_counters = enabled;
+        self
+    }
+
+    /// Also read the voltage of every core from the hardware P-state status
+    ///
+    /// This costs an extra msr read per core for every snapshot,
+    /// the voltages are reported through [`CpuPower::core_voltages`] and [`CpuPower::core_readings`].
+    /// Only available when reading the msrs directly, on Zen 5 the voltage is not reported.
+    pub fn core_voltage(mut self, enabled: bool) -> Self {
+        self.core_voltage = enabled;
+        self
+    }
+
+    /// Override the unit exponents reported by the cpu
+    ///
+    /// Some firmware reports wrong exponents in the power unit msr, leading to readings that are off by a power of two.
+    pub fn unit_overrides(mut self, overrides: UnitOverrides) -> Self {
+        self.unit_overrides = overrides;
+        self
+    }
+
+    /// Use a custom descriptor for the msr addresses and encodings instead of the builtin one for the cpu
+    ///
+    /// Allows using cpus that aren't supported yet, see [`FamilyDescriptor::load`].
+    pub fn descriptor(mut self, descriptor: FamilyDescriptor) -> Self {
+        self.descriptor = Some(descriptor);
+        self
+    }
+
+    pub fn build(self) -> Result<CpuInfo, Error> {
+        CpuInfo::open(self)
+    }
+}
+
+/// Replacement unit exponents for the units decoded from the power unit msr
+///
+/// Every unit is `1 / 2^exponent` of a second, joule or watt, units that are `None` are taken from the msr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UnitOverrides {
+    pub time: Option<u32>,
+    pub energy: Option<u32>,
+    pub power: Option<u32>,
+}
+
+impl UnitOverrides {
+    /// Read the overrides from the `RYZEN_READER_TIME_UNIT`, `RYZEN_READER_ENERGY_UNIT`
+    /// and `RYZEN_READER_POWER_UNIT` environment variables
+    pub fn from_env() -> Self {
+        let exponent = |name| std::env::var(name).ok()?.trim().parse().ok();
+        UnitOverrides {
+            time: exponent("RYZEN_READER_TIME_UNIT"),
+            energy: exponent("RYZEN_READER_ENERGY_UNIT"),
+            power: exponent("RYZEN_READER_POWER_UNIT"),
+        }
+    }
+}
+
+/// Bounded retrying of msr reads that fail with a transient error like `EAGAIN`, `EINTR` or `EBUSY`
+///
+/// Retries are reported in [`ReadStats::retries`], a read is only reported as an error once all retries failed.
+/// The default policy retries twice, starting with a 100µs backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries for a single read
+    pub retries: u32,
+    /// The time to wait before the first retry, doubled for every following retry
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Don't retry failed reads
+    pub fn none() -> Self {
+        RetryPolicy {
+            retries: 0,
+            backoff: Duration::ZERO,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 2,
+            backoff: Duration::from_micros(100),
+        }
+    }
+}
+
+fn is_transient(err: &std::io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EAGAIN) | Some(libc::EINTR) | Some(libc::EBUSY)
+    )
+}
+
+/// How the value of a package is derived from the package counters read through its cores
+///
+/// Every monitored core reads the package counter, since the reads don't happen at the exact same time
+/// the values can differ slightly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PackageAggregation {
+    /// Use the value read through the first core of the package
+    #[default]
+    First,
+    /// Use the average of the values read through all cores of the package
+    Average,
+    /// Use the median of the values read through all cores of the package, ignoring outliers
+    Median,
+}
+
+/// Policy for cleaning up noisy power values of idle cores
+///
+/// The default policy leaves all values untouched.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NoisePolicy {
+    /// Clamp negative values to zero
+    pub clamp_negative: bool,
+    /// Report values with an absolute value below this threshold in watt as zero
+    pub zero_below: Option<f64>,
+}
+
+impl NoisePolicy {
+    /// Clamp negative values to zero and report values below `threshold` watt as zero
+    pub fn denoise(threshold: f64) -> Self {
+        NoisePolicy {
+            clamp_negative: true,
+            zero_below: Some(threshold),
+        }
+    }
+
+    /// Apply the policy to a power value
+    pub fn apply(&self, value: f64) -> f64 {
+        match self.zero_below {
+            Some(threshold) if value.abs() < threshold => 0.0,
+            _ if self.clamp_negative && value < 0.0 => 0.0,
+            _ => value,
+        }
+    }
+}
+
+/// How fine grained the energy counters are
+#[derive(Debug, Clone, Copy)]
+pub struct CounterResolution {
+    /// The energy represented by a single counter increment in joule
+    pub energy: f64,
+    /// The measured average time between counter updates
+    pub update_period: Duration,
+}
+
+/// State for [`CpuInfo::quick_read`]
+struct QuickState {
+    /// Package counters of the last quick read
+    counters: Vec<u64>,
+    time: Option<Instant>,
+}
+
 pub struct CpuInfo {
-    cores: ArrayVec<[Core; 64]>,
+    events: EventLog,
+    cores: Vec<Core>,
+    /// Index of the first core of every package
+    package_cores: Vec<usize>,
     units: PowerUnits,
+    quick: Mutex<QuickState>,
+    accumulator: Mutex<Option<Accumulator>>,
+    noise_policy: NoisePolicy,
+    package_aggregation: PackageAggregation,
+    retry_policy: RetryPolicy,
+    clock: Arc<dyn Clock>,
+    numa_local_reads: bool,
+    frequency_counters: bool,
+    core_voltage: bool,
+    descriptor: FamilyDescriptor,
+    packages_only: bool,
+    single_package_read: bool,
+    fd_budget: usize,
+    /// The cpus monitored cpus are picked from, `None` for all cpus
+    cpu_filter: Option<Vec<u32>>,
+    /// The online cpus at the last (re)scan
+    scanned: Mutex<Vec<u32>>,
+    /// The name of the counter source
+    source: &'static str,
+    /// The additional sources read for every snapshot
+    sources: Vec<(&'static str, CpuInfo)>,
 }
 
 /// Struct that allows reading of cpu power info
 ///
 /// # Example
 ///
-/// ```rust
+/// ```rust,no_run
 /// # use ryzen_reader::{CpuInfo, Error};
 /// #
 /// # fn main() -> Result<(), Error> {
@@ -157,14 +1456,215 @@ pub struct CpuInfo {
 /// #     Ok(())
 /// # }
 ///```
+///
+/// # Counter sources
+///
+/// On Intel cpus the RAPL package and core counters are read from powercap, the core counter only covers all cores
+/// of a package together and is reported for the first cpu of every package.
+/// On AMD cpus, when the `amd_energy` or `zenergy` hwmon driver is loaded the counters are read from it, which doesn't require root
+/// or the msr module. With the `zenpower` driver the package power reported by the voltage regulators is used instead,
+/// which only provides an estimate of the package energy and no core energy.
+/// Otherwise the AMD energy msrs are read through `/dev/cpu/*/msr`. Opening fails with
+/// [`Error::UnsupportedVendor`] on other cpus without sysfs energy counters, like Intel cpus without RAPL powercap zones.
+/// A custom [`PowerBackend`](backend::PowerBackend) can be set with [`CpuInfoBuilder::backend`]
+/// and a specific source with [`CpuInfoBuilder::source`].
+///
+/// # Ordering
+///
+/// Cores are always monitored and reported in ascending order of package id and then cpu id,
+/// independent of the order the kernel lists them in, so positions in readings are stable between runs.
+///
+/// # Forking
+///
+/// All file descriptors opened by this crate are opened with `O_CLOEXEC` and are not leaked into executed programs.
+/// A child created by `fork` without `exec` does share the msr handles with the parent, since msrs are read
+/// without using the file offset this is safe, but the child can call [`reopen`](CpuInfo::reopen) to get its own handles.
+///
+/// # Counter wraparound
+///
+/// The energy counters are 32 bit and wrap around every few minutes under load.
+/// A single wraparound between two snapshots is detected and corrected, the number of corrected wraparounds
+/// is reported in [`SampleQuality`]. With the usual energy unit of ~15.3µJ two snapshots can be at most ~65 seconds
+/// apart before a second wraparound could go unnoticed, see [`max_interval`](CpuInfo::max_interval)
+/// for the exact window of the running cpu.
 impl CpuInfo {
     pub fn new() -> Result<Self, Error> {
-        let mut cores = ArrayVec::<[Core; 64]>::new();
+        Self::builder().build()
+    }
+
+    pub fn builder() -> CpuInfoBuilder {
+        CpuInfoBuilder::default()
+    }
+
+    /// Only monitor the cores in the affinity mask of the calling thread
+    ///
+    /// Useful for benchmarks pinned to a subset of the cores that only want to measure the cores they run on.
+    pub fn for_current_affinity() -> Result<Self, Error> {
+        let status = read_to_string("/proc/thread-self/status")?;
+        let allowed = status
+            .lines()
+            .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+            .ok_or(Error::NoCores)?;
+        let cpus = sysfs::parse_cpu_list(allowed)?;
+
+        Self::with_cores(&cpus)
+    }
+
+    /// Only monitor the given logical cpus
+    ///
+    /// Shorthand for [`CpuInfoBuilder::cpus`], useful when only the cores a workload is pinned to are of interest.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let cpu = CpuInfo::with_cores(&[0, 1, 2, 3])?;
+    ///     let power = cpu.read()?;
+    ///     println!("{:.2}W", power.cores().sum::<f64>());
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn with_cores(cpus: &[u32]) -> Result<Self, Error> {
+        Self::builder().cpus(cpus).build()
+    }
+
+    fn open(builder: CpuInfoBuilder) -> Result<Self, Error> {
+        // custom backends don't depend on the platform
+        if cfg!(not(target_os = "linux")) && builder.backend.is_none() {
+            return Err(Error::UnsupportedPlatform);
+        }
+        let sources = builder
+            .also_read
+            .iter()
+            .map(|source| {
+                let mut builder = builder.clone().source(source.clone());
+                builder.also_read.clear();
+                let cpu = builder.build()?;
+                Ok((cpu.source, cpu))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // the cached physical core and ccd of every cpu, sysfs is read for cpus that aren't cached
+        let mut placements = HashMap::new();
+        let layout = match (&builder.backend, &builder.topology_cache) {
+            (Some(backend), _) => backend.cpus()?,
+            (None, Some(path)) => {
+                let cached = match topology::load_cached(path) {
+                    Some(cached) => cached,
+                    None => {
+                        let cached: Vec<_> = topology::discover(None)?
+                            .into_iter()
+                            .map(|(cpu, package)| topology::CpuPlacement::read(cpu, package))
+                            .collect();
+                        topology::store_cached(path, &cached);
+                        cached
+                    }
+                };
+                cached
+                    .into_iter()
+                    .map(|placement| {
+                        placements.insert(placement.cpu, (placement.physical, placement.ccd));
+                        (placement.cpu, placement.package)
+                    })
+                    .collect()
+            }
+            (None, None) => topology::discover(builder.cpus.as_deref())?,
+        };
+
+        let mut layout: Vec<_> = match &builder.cpus {
+            Some(cpus) => layout
+                .into_iter()
+                .filter(|(cpu, _)| cpus.contains(cpu))
+                .collect(),
+            None => layout,
+        };
+        // cores are always ordered by package and cpu id, regardless of the order they were discovered in
+        layout.sort_unstable_by_key(|(cpu, package)| (*package, *cpu));
+
+        let fd_budget = match builder.fd_budget {
+            Some(budget) => budget,
+            None => default_fd_budget(),
+        };
+        let mut hwmon = match (&builder.backend, builder.sysfs) {
+            (Some(_), _) | (None, Some(false)) => None,
+            (None, Some(true)) => Some(hwmon::find().ok_or(Error::SourceUnavailable)?),
+            (None, None) => hwmon::find(),
+        };
+        // the msr layout is AMD specific, other vendors can only be read through sysfs
+        if builder.backend.is_none() && hwmon.is_none() {
+            hwmon::msr_vendor_supported().map_err(Error::UnsupportedVendor)?;
+        }
+        let source = match (&builder.backend, &hwmon) {
+            (Some(_), _) => "backend",
+            (None, Some(device)) => device.name(),
+            (None, None) => "msr",
+        };
+        // only the msr source keeps file descriptors open
+        let fd_budget = if hwmon.is_some() || builder.backend.is_some() {
+            usize::MAX
+        } else {
+            fd_budget
+        };
+        let packages_only = builder.packages_only || layout.len() > fd_budget;
+        let layout: Vec<_> = if packages_only {
+            let mut seen = Vec::new();
+            layout
+                .into_iter()
+                .filter(|(_, package)| {
+                    let first = !seen.contains(package);
+                    seen.push(*package);
+                    first
+                })
+                .collect()
+        } else {
+            layout
+        };
+        if layout.len() > fd_budget {
+            return Err(Error::FdBudgetExceeded {
+                needed: layout.len(),
+                budget: fd_budget,
+            });
+        }
+
+        // only the msr source depends on the msr layout
+        let descriptor = match (builder.descriptor, &builder.backend, &hwmon) {
+            (Some(descriptor), _, _) => descriptor,
+            (None, None, None) => FamilyDescriptor::current(),
+            _ => FamilyDescriptor::default(),
+        };
+        let mut cores = Vec::with_capacity(layout.len());
 
-        for i in 0..MAX_CPUS {
-            match Core::open(i) {
-                Ok(core) => cores.push(core),
-                Err(Error::CoreNotFound) => break,
+        for (cpu, package) in layout {
+            let (physical, ccd) = match builder.backend {
+                Some(_) => (cpu, None),
+                None => placements
+                    .get(&cpu)
+                    .copied()
+                    .unwrap_or_else(|| (topology::physical_core(cpu), topology::ccd(cpu))),
+            };
+            let core = match (&builder.backend, &mut hwmon) {
+                (Some(backend), _) => Ok(Core::with_source(
+                    CounterSource::Backend(backend.clone()),
+                    cpu,
+                    package,
+                )),
+                (None, Some(device)) => Ok(Core::with_source(
+                    CounterSource::Hwmon(device.counters(physical, package)),
+                    cpu,
+                    package,
+                )),
+                (None, None) => Core::open(cpu, package),
+            };
+            match core {
+                Ok(mut core) => {
+                    core.physical = physical;
+                    core.ccd = ccd;
+                    core.descriptor = descriptor;
+                    cores.push(core)
+                }
+                Err(Error::CoreNotFound) => continue,
                 Err(e) => return Err(e),
             }
         }
@@ -174,9 +1674,16 @@ impl CpuInfo {
         }
 
         let units = cores[0].read(MsrValue::PowerUnit)?;
-        let time_unit = (units & AMD_TIME_UNIT_MASK) >> 16;
-        let energy_unit = (units & AMD_ENERGY_UNIT_MASK) >> 8;
-        let power_unit = units & AMD_POWER_UNIT_MASK;
+        let overrides = builder.unit_overrides;
+        let time_unit = overrides
+            .time
+            .unwrap_or(descriptor::field(units, descriptor.time_unit_mask) as u32);
+        let energy_unit = overrides
+            .energy
+            .unwrap_or(descriptor::field(units, descriptor.energy_unit_mask) as u32);
+        let power_unit = overrides
+            .power
+            .unwrap_or(descriptor::field(units, descriptor.power_unit_mask) as u32);
 
         let time_unit = 0.5f64.powi(time_unit as i32);
         let energy_unit = 0.5f64.powi(energy_unit as i32);
@@ -188,44 +1695,473 @@ impl CpuInfo {
             power_unit,
         };
 
-        Ok(CpuInfo { cores, units })
+        let package_cores = first_package_cores(&cores);
+        if builder.frequency_counters {
+            for core in cores
+                .iter_mut()
+                .filter(|core| matches!(core.source, CounterSource::Msr(_)))
+            {
+                core.base_frequency = core.read_base_frequency()?;
+            }
+        }
+        if builder.single_package_read {
+            for (index, core) in cores.iter_mut().enumerate() {
+                core.reads_package = package_cores.contains(&index);
+            }
+        }
+        let quick = Mutex::new(QuickState {
+            counters: vec![0; package_cores.len()],
+            time: None,
+        });
+
+        let cpu = CpuInfo {
+            events: EventLog::default(),
+            cores,
+            package_cores,
+            units,
+            quick,
+            accumulator: Mutex::new(None),
+            noise_policy: builder.noise_policy,
+            package_aggregation: builder.package_aggregation,
+            retry_policy: builder.retry_policy,
+            clock: builder.clock.unwrap_or_else(|| Arc::new(SystemClock)),
+            numa_local_reads: builder.numa_local_reads,
+            frequency_counters: builder.frequency_counters,
+            core_voltage: builder.core_voltage,
+            descriptor,
+            packages_only,
+            single_package_read: builder.single_package_read,
+            fd_budget,
+            cpu_filter: builder.cpus,
+            scanned: Mutex::new(topology::online_cpus().unwrap_or_default()),
+            source,
+            sources,
+        };
+        cpu.events.push(Event::SourceSelected { source });
+        cpu.snapshot()?;
+
+        Ok(cpu)
+    }
+
+    /// Re-open all msr handles
+    ///
+    /// This can be called in a forked child process to stop sharing handles with the parent.
+    pub fn reopen(&mut self) -> Result<(), Error> {
+        for (_, source) in &mut self.sources {
+            source.reopen()?;
+        }
+        self.cores.iter_mut().try_for_each(Core::reopen)
+    }
+
+    /// The name of the counter source, `msr`, `backend` or the name of the sysfs driver
+    pub fn source(&self) -> &'static str {
+        self.source
+    }
+
+    /// Check which of the monitored cpus are online
+    ///
+    /// Cpus that are taken offline are detected when reading them fails and are skipped from then on,
+    /// their core power is reported as unavailable. Offline cpus are checked again on every read and
+    /// picked up once they come back online. Calling `refresh` after changing which cpus are online
+    /// updates all cores at once instead.
+    ///
+    /// Cpus that were offline when the [`CpuInfo`] was created are not monitored.
+    pub fn refresh(&self) -> Result<(), Error> {
+        let online = topology::online_cpus()?;
+        for core in &self.cores {
+            self.update_online(core, online.contains(&core.cpu));
+        }
+        Ok(())
+    }
+
+    /// Whether the set of online cpus changed since the `CpuInfo` was created or last rescanned
+    pub fn rescan_needed(&self) -> bool {
+        topology::online_cpus().is_ok_and(|online| online != *self.scanned.lock().unwrap())
+    }
+
+    /// Start monitoring cpus that came online after the `CpuInfo` was created
+    ///
+    /// Picks up cpus and whole packages brought online later, like servers booted with `maxcpus=` or with nodes
+    /// onlined at runtime, and updates which monitored cpus are online as with [`refresh`](CpuInfo::refresh).
+    /// Added cpus are placed in the usual order, snapshots taken before the rescan are matched to the cores by cpu id
+    /// and added cores report no power for the first interval.
+    ///
+    /// Only the msr source can add cpus, the sysfs drivers only provide counters for the cpus present when they were loaded.
+    /// Returns the ids of the added cpus.
+    pub fn rescan(&mut self) -> Result<Vec<u32>, Error> {
+        let online = topology::online_cpus()?;
+        let msr_source = matches!(self.cores[0].source, CounterSource::Msr(_));
+        let mut added = Vec::new();
+        if msr_source {
+            let new: Vec<_> = topology::discover(self.cpu_filter.as_deref())?
+                .into_iter()
+                .filter(|(cpu, _)| !self.cores.iter().any(|core| core.cpu == *cpu))
+                .collect();
+            for (cpu, package) in new {
+                if self.packages_only && self.cores.iter().any(|core| core.package == package) {
+                    continue;
+                }
+                if self.cores.len() >= self.fd_budget {
+                    return Err(Error::FdBudgetExceeded {
+                        needed: self.cores.len() + 1,
+                        budget: self.fd_budget,
+                    });
+                }
+                let mut core = match Core::open(cpu, package) {
+                    Ok(core) => core,
+                    Err(Error::CoreNotFound) => continue,
+                    Err(e) => return Err(e),
+                };
+                core.physical = topology::physical_core(cpu);
+                core.ccd = topology::ccd(cpu);
+                core.descriptor = self.descriptor;
+                if self.frequency_counters {
+                    core.base_frequency = core.read_base_frequency()?;
+                }
+                self.cores.push(core);
+                self.events.push(Event::CpuAdded { cpu });
+                added.push(cpu);
+            }
+        }
+
+        if !added.is_empty() {
+            self.cores
+                .sort_unstable_by_key(|core| (core.package, core.cpu));
+            self.package_cores = first_package_cores(&self.cores);
+            if self.single_package_read {
+                for (index, core) in self.cores.iter_mut().enumerate() {
+                    core.reads_package = self.package_cores.contains(&index);
+                }
+            }
+            *self.quick.get_mut().unwrap() = QuickState {
+                counters: vec![0; self.package_cores.len()],
+                time: None,
+            };
+        }
+        if msr_source {
+            for core in &self.cores {
+                self.update_online(core, online.contains(&core.cpu));
+            }
+        }
+        *self.scanned.get_mut().unwrap() = online;
+        Ok(added)
+    }
+
+    fn update_online(&self, core: &Core, online: bool) {
+        if core.set_online(online) {
+            self.events.push(match online {
+                true => Event::CpuOnline { cpu: core.cpu },
+                false => Event::CpuOffline { cpu: core.cpu },
+            });
+        }
+    }
+
+    /// The longest interval between two reads for which the energy counters can not wrap around more than once
+    ///
+    /// A single wraparound between two reads is corrected, power calculated over longer intervals would be ambiguous.
+    /// The interval is derived from the energy unit and an upper bound of 1000W per package.
+    pub fn max_interval(&self) -> Duration {
+        max_interval(self.units.energy_unit)
+    }
+
+    /// The msr addresses and encodings used to read this cpu
+    pub fn descriptor(&self) -> &FamilyDescriptor {
+        &self.descriptor
+    }
+
+    /// The energy domains and msrs read for the running cpu with the active descriptor
+    pub fn supported_domains(&self) -> Option<support::SupportedDomains> {
+        support::current_cpu()
+            .map(|(family, model)| support::supported_domains_with(family, model, &self.descriptor))
+    }
+
+    /// The number of file descriptors held open for msr handles
+    pub fn open_descriptors(&self) -> usize {
+        self.cores
+            .iter()
+            .filter(|core| matches!(core.source, CounterSource::Msr(_)))
+            .count()
+    }
+
+    /// Whether only a single cpu per package is monitored, either because it was requested
+    /// or because monitoring all cpus would exceed the file descriptor budget
+    pub fn packages_only(&self) -> bool {
+        self.packages_only
+    }
+
+    /// Detect the resolution and update period of the energy counters
+    ///
+    /// The update period is measured by polling the package energy counter of the first core,
+    /// sampling intervals shorter than the update period will give very noisy results.
+    ///
+    /// Note that this method will busy-wait for ~50ms
+    pub fn counter_resolution(&self) -> Result<CounterResolution, Error> {
+        let core = &self.cores[0];
+        let start = Instant::now();
+        let mut last = core.read(MsrValue::PackageEnergy)?;
+        let mut first_update = None;
+        let mut last_update = start;
+        let mut updates = 0u32;
+
+        while start.elapsed() < UPDATE_MEASURE_TIME {
+            let value = core.read(MsrValue::PackageEnergy)?;
+            if value != last {
+                let now = Instant::now();
+                // the period until the first update is incomplete
+                if first_update.is_none() {
+                    first_update = Some(now);
+                } else {
+                    updates += 1;
+                }
+                last_update = now;
+                last = value;
+            }
+        }
+
+        let update_period = match first_update {
+            Some(first_update) if updates > 0 => last_update.duration_since(first_update) / updates,
+            // too few updates to measure, the period is at least as long as we've been watching
+            _ => UPDATE_MEASURE_TIME,
+        };
+
+        Ok(CounterResolution {
+            energy: self.units.energy_unit,
+            update_period,
+        })
     }
 
     /// Read the cpu power levels
     ///
     /// Note that this method will block for ~10ms
     pub fn read(&self) -> Result<CpuPower, Error> {
-        let start = self
-            .read_raw()
-            .collect::<Result<ArrayVec<[CorePower; 64]>, Error>>()?;
-        sleep(Duration::from_millis(10));
+        self.read_with_interval(Duration::from_millis(10))
+    }
 
-        let cores = start
-            .into_iter()
-            .zip(self.read_raw())
-            .map(|(start, end)| {
-                let end = end?;
-                Ok(CorePower {
-                    core_power: (end.core_power - start.core_power) * 100.0,
-                    package_power: (end.package_power - start.package_power) * 100.0,
-                    package: start.package,
+    /// Read the cpu power levels averaged over `interval`
+    ///
+    /// Longer intervals give less noisy readings on lightly loaded systems at the cost of latency.
+    /// Intervals longer than [`max_interval`](CpuInfo::max_interval) are rejected with [`Error::IntervalTooLong`].
+    ///
+    /// Note that this method will block for `interval`
+    pub fn read_with_interval(&self, interval: Duration) -> Result<CpuPower, Error> {
+        let max = self.max_interval();
+        if interval > max {
+            return Err(Error::IntervalTooLong { interval, max });
+        }
+
+        let start = self.snapshot()?;
+        sleep(interval);
+        let end = self.snapshot()?;
+
+        CpuPower::between(&start, &end)
+    }
+
+    /// Get the total package power since the previous call to this method in watt
+    ///
+    /// Intended for overlays and other consumers polling at a high rate, this only reads one msr per package,
+    /// doesn't block and doesn't allocate.
+    /// Returns `None` on the first call, when there is no previous reading to compare to.
+    pub fn quick_read(&self) -> Result<Option<f64>, Error> {
+        let mut quick = self.quick.lock().unwrap();
+        let QuickState { counters, time } = &mut *quick;
+
+        let now = self.clock.now();
+        let mut energy = 0;
+        for (core, last) in self.package_cores.iter().zip(counters.iter_mut()) {
+            if let Some(counter) = self.cores[*core].read_domain(MsrValue::PackageEnergy)? {
+                energy += counter_delta(*last, counter).0;
+                *last = counter;
+            }
+        }
+
+        let previous = time.replace(now);
+        Ok(previous
+            .map(|previous| now.saturating_duration_since(previous))
+            .filter(|interval| !interval.is_zero())
+            .map(|interval| {
+                let power = energy as f64 * self.units.energy_unit / interval.as_secs_f64();
+                self.noise_policy.apply(power)
+            }))
+    }
+
+    /// Get the raw msr values last read from every monitored cpu
+    ///
+    /// Intended for debugging and bug reports, the [`Display`](fmt::Display) output of the values can be pasted as-is.
+    pub fn raw_values(&self) -> Vec<RawMsrValues> {
+        self.cores
+            .iter()
+            .map(|core| *core.last_raw.lock().unwrap())
+            .collect()
+    }
+
+    /// Get the topology of the monitored cpus, including the current cpufreq scaling state
+    pub fn topology(&self) -> Topology {
+        Topology {
+            cores: self
+                .cores
+                .iter()
+                .map(|core| CoreTopology {
+                    cpu: core.cpu,
+                    package: core.package,
+                    frequency: FrequencyInfo::read(core.cpu),
+                    amd_pstate: AmdPstateInfo::read(core.cpu),
+                    core_energy: core.core_energy_available(),
+                    package_energy: core.package_energy_available(),
                 })
-            })
-            .collect::<Result<ArrayVec<[CorePower; 64]>, Error>>()?;
+                .collect(),
+        }
+    }
+
+    fn read_counters(&self, core: &Core, stats: &mut ReadStats) -> Result<RawCounters, Error> {
+        let used_ioctl = core.use_ioctl.load(Ordering::Relaxed);
+        let core_available = core.core_energy_available();
+        let package_available = core.package_energy_available();
+        if !core.is_online() {
+            self.update_online(core, topology::is_online(core.cpu));
+        }
+        let was_online = core.is_online();
+        let start = Instant::now();
+        let mut counters = RawCounters {
+            core_energy: core.read_domain_retrying(
+                MsrValue::CoreEnergy,
+                self.retry_policy,
+                stats,
+            )?,
+            package_energy: if core.reads_package {
+                core.read_domain_retrying(MsrValue::PackageEnergy, self.retry_policy, stats)?
+            } else {
+                None
+            },
+            cpu: core.cpu,
+            package: core.package,
+            physical: core.physical,
+            ccd: core.ccd,
+            aperf: None,
+            mperf: None,
+            pstate: None,
+            base_frequency: core.base_frequency,
+            voltage: None,
+            time: self.clock.now(),
+        };
+        if self.core_voltage
+            && self.descriptor.voltage != VoltageEncoding::Unknown
+            && matches!(core.source, CounterSource::Msr(_))
+            && core.is_online()
+        {
+            counters.voltage = core
+                .read_optional(MsrValue::HwPStateStatus)?
+                .and_then(|status| self.descriptor.decode_voltage(status));
+            stats.syscalls += 1;
+        }
+        if self.frequency_counters && core.base_frequency.is_some() && core.is_online() {
+            counters.mperf = core.read_optional(MsrValue::Mperf)?;
+            counters.aperf = core.read_optional(MsrValue::Aperf)?;
+            counters.pstate = core
+                .read_optional(MsrValue::PStateStatus)?
+                .map(|status| (status & 0x7) as u32);
+            stats.syscalls += 3;
+        }
+        let latency = start.elapsed();
 
-        Ok(CpuPower { cores })
+        if !used_ioctl && core.use_ioctl.load(Ordering::Relaxed) {
+            self.events.push(Event::IoctlFallback { cpu: core.cpu });
+        }
+        if was_online && !core.is_online() {
+            self.events.push(Event::CpuOffline { cpu: core.cpu });
+        }
+        if core_available && !core.core_energy_available() {
+            self.events.push(Event::DomainUnavailable {
+                cpu: core.cpu,
+                domain: "core",
+            });
+        }
+        if package_available && !core.package_energy_available() {
+            self.events.push(Event::DomainUnavailable {
+                cpu: core.cpu,
+                domain: "package",
+            });
+        }
+
+        stats.total_latency += latency;
+        stats.per_core_latency_max = stats.per_core_latency_max.max(latency);
+        Ok(counters)
     }
 
-    fn read_raw<'a>(&'a self) -> impl Iterator<Item = Result<CorePower, Error>> + 'a {
-        let energy_unit = self.units.energy_unit;
-        self.cores.iter().map(move |core: &'a Core| {
-            let core_power = core.read(MsrValue::CoreEnergy)? as f64 * energy_unit;
-            let package_power = core.read(MsrValue::PackageEnergy)? as f64 * energy_unit;
-            Ok(CorePower {
-                core_power,
-                package_power,
-                package: core.package,
+    /// Read the counters one package at a time while running on that package
+    fn read_counters_numa_local(&self, stats: &mut ReadStats) -> Result<Vec<RawCounters>, Error> {
+        let mut counters = vec![None; self.cores.len()];
+        for package in self
+            .package_cores
+            .iter()
+            .map(|index| self.cores[*index].package)
+        {
+            let package_cores = || {
+                self.cores
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, core)| core.package == package)
+            };
+            let _guard = affinity::pin_to(package_cores().map(|(_, core)| core.cpu))?;
+            // getting, setting and restoring the affinity
+            stats.syscalls += 3;
+            for (index, core) in package_cores() {
+                counters[index] = Some(self.read_counters(core, stats)?);
+            }
+        }
+
+        Ok(counters
+            .into_iter()
+            .map(|counters| counters.expect("all packages are read"))
+            .collect())
+    }
+
+    /// Read the energy counters of all cores without blocking
+    ///
+    /// Use [`CpuPower::between`] to get the power levels between two snapshots.
+    pub fn snapshot(&self) -> Result<EnergySnapshot, Error> {
+        self.read_snapshot().inspect_err(|e| {
+            self.events.push(Event::ReadError {
+                message: e.to_string(),
             })
         })
     }
+
+    /// Get the events logged while opening and reading the counters, oldest first
+    ///
+    /// Only the most recent 256 events are kept.
+    pub fn events(&self) -> Vec<LoggedEvent> {
+        self.events.events()
+    }
+
+    fn read_snapshot(&self) -> Result<EnergySnapshot, Error> {
+        let time = self.clock.now();
+        let mut stats = ReadStats::default();
+        let counters = if self.numa_local_reads {
+            self.read_counters_numa_local(&mut stats)?
+        } else {
+            self.cores
+                .iter()
+                .map(|core| self.read_counters(core, &mut stats))
+                .collect::<Result<Vec<RawCounters>, Error>>()?
+        };
+
+        let snapshot = EnergySnapshot {
+            counters,
+            time,
+            spread: self.clock.now().saturating_duration_since(time),
+            stats,
+            energy_unit: self.units.energy_unit,
+            noise_policy: self.noise_policy,
+            package_aggregation: self.package_aggregation,
+            suspend_clock: boot_time().map(|boot_time| (Instant::now(), boot_time)),
+            sources: self
+                .sources
+                .iter()
+                .map(|(name, cpu)| Ok((*name, cpu.snapshot()?)))
+                .collect::<Result<_, Error>>()?,
+        };
+        self.accumulate(&snapshot);
+
+        Ok(snapshot)
+    }
 }
@@ -0,0 +1,227 @@
+//! Reproducible cpu load for calibration and power measurements
+//!
+//! A [`LoadGenerator`] runs a spin loop pinned to each requested cpu, busy for a fraction of every period
+//! and sleeping for the rest. The time the load was started is recorded so it can be lined up with samples.
+
+use crate::{affinity, Error};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, sleep, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The shape of the load generated on every cpu
+#[derive(Debug, Clone, Copy)]
+pub struct LoadPattern {
+    duty: f64,
+    period: Duration,
+    avx: bool,
+}
+
+impl LoadPattern {
+    /// Keep the cpu busy for `duty` of every 100ms period using scalar instructions
+    ///
+    /// The duty is clamped between 0 and 1, a NaN duty is rejected by [`LoadGenerator::start`].
+    pub fn duty(duty: f64) -> Self {
+        LoadPattern {
+            duty: duty.clamp(0.0, 1.0),
+            period: Duration::from_millis(100),
+            avx: false,
+        }
+    }
+
+    /// Keep the cpu fully busy
+    pub fn full() -> Self {
+        Self::duty(1.0)
+    }
+
+    /// The length of a single busy/idle cycle, zero periods are rejected by [`LoadGenerator::start`]
+    pub fn with_period(self, period: Duration) -> Self {
+        LoadPattern { period, ..self }
+    }
+
+    /// Use AVX instructions for the busy part, if the cpu supports them
+    pub fn with_avx(self, avx: bool) -> Self {
+        LoadPattern { avx, ..self }
+    }
+
+    /// Fraction of every period the cpu is kept busy, between 0 and 1
+    pub fn duty_cycle(&self) -> f64 {
+        self.duty
+    }
+
+    /// Length of a single busy/idle cycle
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Whether AVX instructions are requested for the busy part
+    pub fn avx(&self) -> bool {
+        self.avx
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if !(0.0..=1.0).contains(&self.duty) {
+            return Err(Error::InvalidLoadPattern(
+                "the duty has to be between 0 and 1",
+            ));
+        }
+        if self.period.is_zero() {
+            return Err(Error::InvalidLoadPattern("the period can't be zero"));
+        }
+        Ok(())
+    }
+}
+
+/// Load running on a set of cpus, stopped when dropped
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::{CpuInfo, Error};
+/// # use ryzen_reader::load::{LoadGenerator, LoadPattern};
+/// #
+/// # fn main() -> Result<(), Error> {
+///     let cpu = CpuInfo::new()?;
+///     let load = LoadGenerator::start(&[0, 1], LoadPattern::duty(0.5).with_avx(true))?;
+///     let power = cpu.read()?;
+///     println!("{:.2}W at 50% load since {:?}", power.packages().sum::<f64>(), load.started());
+///     load.stop();
+/// #     Ok(())
+/// # }
+///```
+pub struct LoadGenerator {
+    threads: Vec<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    started: Instant,
+}
+
+impl LoadGenerator {
+    /// Start generating load on every cpu in `cpus`
+    ///
+    /// Returns once all load threads are pinned to their cpu.
+    /// Patterns with a NaN duty or a zero period are rejected with [`Error::InvalidLoadPattern`].
+    pub fn start(cpus: &[u32], pattern: LoadPattern) -> Result<Self, Error> {
+        pattern.validate()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut generator = LoadGenerator {
+            threads: Vec::with_capacity(cpus.len()),
+            stop,
+            started: Instant::now(),
+        };
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        for &cpu in cpus {
+            let stop = generator.stop.clone();
+            let ready = ready_tx.clone();
+            let thread = thread::Builder::new()
+                .name(format!("load-{}", cpu))
+                .spawn(move || {
+                    let _guard = match affinity::pin_to([cpu]) {
+                        Ok(guard) => {
+                            let _ = ready.send(Ok(()));
+                            guard
+                        }
+                        Err(e) => {
+                            let _ = ready.send(Err(e));
+                            return;
+                        }
+                    };
+                    drop(ready);
+                    run(pattern, &stop);
+                })
+                .map_err(Error::Spawn)?;
+            generator.threads.push(thread);
+        }
+        drop(ready_tx);
+
+        for result in ready_rx {
+            result?;
+        }
+        generator.started = Instant::now();
+
+        Ok(generator)
+    }
+
+    /// The time all load threads were running
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// Stop the load and wait for all load threads to exit
+    pub fn stop(self) {}
+}
+
+impl Drop for LoadGenerator {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(pattern: LoadPattern, stop: &AtomicBool) {
+    let busy = pattern.period.mul_f64(pattern.duty);
+    let idle = pattern.period.saturating_sub(busy);
+    let avx = pattern.avx && avx_supported();
+
+    while !stop.load(Ordering::Relaxed) {
+        let start = Instant::now();
+        while start.elapsed() < busy {
+            if avx {
+                spin_avx();
+            } else {
+                spin_scalar();
+            }
+        }
+        if !idle.is_zero() {
+            sleep(idle);
+        }
+    }
+}
+
+const SPIN_ITERATIONS: usize = 1024;
+
+fn spin_scalar() {
+    let mut value = 1.0f64;
+    for _ in 0..SPIN_ITERATIONS {
+        value = black_box(value * 1.000_001 + 0.000_001);
+    }
+    black_box(value);
+}
+
+#[cfg(target_arch = "x86_64")]
+fn avx_supported() -> bool {
+    is_x86_feature_detected!("avx")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn avx_supported() -> bool {
+    false
+}
+
+#[cfg(target_arch = "x86_64")]
+fn spin_avx() {
+    // safety: only called after checking that the cpu supports avx
+    unsafe { spin_avx_inner() }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn spin_avx() {
+    spin_scalar()
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+unsafe fn spin_avx_inner() {
+    use std::arch::x86_64::*;
+
+    let factor = _mm256_set1_pd(1.000_001);
+    let offset = _mm256_set1_pd(0.000_001);
+    let mut value = _mm256_set1_pd(1.0);
+    for _ in 0..SPIN_ITERATIONS {
+        value = black_box(_mm256_add_pd(_mm256_mul_pd(value, factor), offset));
+    }
+    black_box(value);
+}
@@ -0,0 +1,71 @@
+//! Single package power readings with the smallest possible footprint
+//!
+//! Intended for early boot or initramfs diagnostics that only need the power of one package.
+//! A [`PackageReader`] opens a single msr handle, doesn't discover the topology, doesn't spawn threads
+//! and doesn't allocate after it has been opened.
+
+use crate::descriptor::field;
+use crate::{counter_delta, Core, Error, MsrValue};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Reads the package power through a single cpu
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::{minimal::PackageReader, Error};
+/// # use std::time::Duration;
+/// #
+/// # fn main() -> Result<(), Error> {
+///     let power = PackageReader::open(0)?.read_over(Duration::from_millis(100))?;
+///     println!("{:.2}W", power);
+/// #     Ok(())
+/// # }
+///```
+pub struct PackageReader {
+    core: Core,
+    energy_unit: f64,
+    last: Option<(u64, Instant)>,
+}
+
+impl PackageReader {
+    /// Open the msr handle of `cpu` to read the power of the package it belongs to
+    pub fn open(cpu: u32) -> Result<Self, Error> {
+        // the package id is only used for grouping, which a single reader doesn't do
+        let core = Core::open(cpu, 0)?;
+        let units = core.read(MsrValue::PowerUnit)?;
+        let energy_unit = 0.5f64.powi(field(units, core.descriptor.energy_unit_mask) as i32);
+
+        Ok(PackageReader {
+            core,
+            energy_unit,
+            last: None,
+        })
+    }
+
+    /// Get the package power since the previous call in watt
+    ///
+    /// Returns `None` on the first call, when there is no previous reading to compare to.
+    pub fn read(&mut self) -> Result<Option<f64>, Error> {
+        let now = Instant::now();
+        let counter = self
+            .core
+            .read_domain(MsrValue::PackageEnergy)?
+            .ok_or(Error::PackageEnergyUnavailable(self.core.cpu))?;
+
+        Ok(self.last.replace((counter, now)).map(|(last, previous)| {
+            let energy = counter_delta(last, counter).0 as f64 * self.energy_unit;
+            energy / now.duration_since(previous).as_secs_f64()
+        }))
+    }
+
+    /// Get the average package power over `interval` in watt
+    ///
+    /// Note that this method will block for `interval`
+    pub fn read_over(&mut self, interval: Duration) -> Result<f64, Error> {
+        self.read()?;
+        sleep(interval);
+        Ok(self.read()?.unwrap_or_default())
+    }
+}
@@ -0,0 +1,374 @@
+//! Continuous power readings at a fixed interval
+
+use crate::{CpuInfo, CpuPower, EnergySnapshot, Error};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, sleep, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Relative change in package power between readings above which the adaptive interval is shortened
+const ADAPTIVE_BURST_CHANGE: f64 = 0.1;
+/// Relative change in package power between readings below which the adaptive interval is lengthened
+const ADAPTIVE_STABLE_CHANGE: f64 = 0.02;
+
+/// Iterator yielding a [`CpuPower`] every interval, created by [`CpuInfo::monitor`]
+///
+/// Every reading starts at the snapshot the previous reading ended with,
+/// so consecutive readings cover the monitored time without gaps and only a single snapshot is taken per reading.
+///
+/// The iterator never ends, a failed reading is yielded as an error and the next reading continues
+/// from the last successful snapshot.
+///
+/// With [`adaptive`](Monitor::adaptive) the interval follows how fast the power changes.
+pub struct Monitor<'a> {
+    cpu: &'a CpuInfo,
+    interval: Duration,
+    last: EnergySnapshot,
+    deadline: Instant,
+    /// The bounds of the adaptive interval, `None` for a fixed interval
+    bounds: Option<(Duration, Duration)>,
+    last_power: Option<f64>,
+}
+
+impl CpuInfo {
+    /// Yield the cpu power levels averaged over every `interval`
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// # use std::time::Duration;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let cpu = CpuInfo::new()?;
+    ///     for power in cpu.monitor(Duration::from_secs(1))? {
+    ///         println!("{:.2}W", power?.packages().sum::<f64>());
+    ///     }
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn monitor(&self, interval: Duration) -> Result<Monitor<'_>, Error> {
+        if interval.is_zero() {
+            return Err(Error::EmptyInterval);
+        }
+        let max = self.max_interval();
+        if interval > max {
+            return Err(Error::IntervalTooLong { interval, max });
+        }
+
+        Ok(Monitor {
+            cpu: self,
+            interval,
+            last: self.snapshot()?,
+            deadline: Instant::now() + interval,
+            bounds: None,
+            last_power: None,
+        })
+    }
+}
+
+impl Monitor<'_> {
+    /// The interval between readings
+    ///
+    /// For an adaptive monitor this is the interval until the next reading.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Adapt the interval to how fast the package power changes, within `min` and `max`
+    ///
+    /// The interval is halved when the total package power changes by more than 10% between readings
+    /// and grows by half when it changes by less than 2%, so idle systems are read less often
+    /// while bursts are followed closely.
+    ///
+    /// A zero `min` is rejected with [`Error::EmptyInterval`], a `min` above `max` with [`Error::InvalidIntervalBounds`]
+    /// and a `max` above [`CpuInfo::max_interval`] with [`Error::IntervalTooLong`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// # use std::time::Duration;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let cpu = CpuInfo::new()?;
+    ///     let monitor = cpu
+    ///         .monitor(Duration::from_secs(1))?
+    ///         .adaptive(Duration::from_millis(100), Duration::from_secs(5))?;
+    ///     for power in monitor {
+    ///         let power = power?;
+    ///         println!("{:.2}W over {:?}", power.packages().sum::<f64>(), power.quality().interval);
+    ///     }
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn adaptive(mut self, min: Duration, max: Duration) -> Result<Self, Error> {
+        if min.is_zero() {
+            return Err(Error::EmptyInterval);
+        }
+        if min > max {
+            return Err(Error::InvalidIntervalBounds { min, max });
+        }
+        let limit = self.cpu.max_interval();
+        if max > limit {
+            return Err(Error::IntervalTooLong {
+                interval: max,
+                max: limit,
+            });
+        }
+        self.interval = self.interval.clamp(min, max);
+        self.deadline = Instant::now() + self.interval;
+        self.bounds = Some((min, max));
+        Ok(self)
+    }
+
+    /// Pick the next interval from the change in power of a reading
+    fn adapt(&mut self, power: &CpuPower) {
+        let (min, max) = match self.bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let total = power.packages().sum::<f64>();
+        if let Some(last) = self.last_power.replace(total) {
+            // don't let small absolute changes at near zero power count as large relative changes
+            let change = (total - last).abs() / last.max(1.0);
+            if change > ADAPTIVE_BURST_CHANGE {
+                self.interval = (self.interval / 2).max(min);
+            } else if change < ADAPTIVE_STABLE_CHANGE {
+                self.interval = self.interval.mul_f64(1.5).min(max);
+            }
+        }
+    }
+}
+
+impl Iterator for Monitor<'_> {
+    type Item = Result<CpuPower, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        sleep(self.deadline.saturating_duration_since(Instant::now()));
+        let now = Instant::now();
+
+        let snapshot = match self.cpu.snapshot() {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                self.deadline = now + self.interval;
+                return Some(Err(e));
+            }
+        };
+        let power = CpuPower::between(&self.last, &snapshot);
+        self.last = snapshot;
+        if let Ok(power) = &power {
+            self.adapt(power);
+        }
+        // schedule from the current time instead of the previous deadline to not burst after a slow consumer
+        self.deadline = now + self.interval;
+        Some(power)
+    }
+}
+
+/// Background sampling thread created by [`CpuInfo::subscribe`], stopped when dropped
+pub struct Subscriber {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CpuInfo {
+    /// Call `callback` with the cpu power levels averaged over every `interval` from a background thread
+    ///
+    /// Failed readings are passed to the callback as an error and sampling continues,
+    /// sampling stops when the returned [`Subscriber`] is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let cpu = Arc::new(CpuInfo::new()?);
+    ///     let subscriber = cpu.subscribe(Duration::from_secs(1), |power| match power {
+    ///         Ok(power) => println!("{:.2}W", power.packages().sum::<f64>()),
+    ///         Err(e) => eprintln!("{}", e),
+    ///     })?;
+    ///     std::thread::sleep(Duration::from_secs(10));
+    ///     subscriber.stop();
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn subscribe<F>(
+        self: &Arc<Self>,
+        interval: Duration,
+        mut callback: F,
+    ) -> Result<Subscriber, Error>
+    where
+        F: FnMut(Result<CpuPower, Error>) + Send + 'static,
+    {
+        if interval.is_zero() {
+            return Err(Error::EmptyInterval);
+        }
+        let max = self.max_interval();
+        if interval > max {
+            return Err(Error::IntervalTooLong { interval, max });
+        }
+
+        let cpu = self.clone();
+        let mut last = cpu.snapshot()?;
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::Builder::new()
+            .name("ryzen-reader-subscriber".into())
+            .spawn(move || {
+                let mut deadline = Instant::now() + interval;
+                // the sender is never used, it being dropped signals the thread to stop
+                while let Err(RecvTimeoutError::Timeout) =
+                    stopped.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                {
+                    deadline = Instant::now() + interval;
+                    match cpu.snapshot() {
+                        Ok(snapshot) => {
+                            callback(CpuPower::between(&last, &snapshot));
+                            last = snapshot;
+                        }
+                        Err(e) => callback(Err(e)),
+                    }
+                }
+            })
+            .map_err(Error::Spawn)?;
+
+        Ok(Subscriber {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Subscriber {
+    /// Stop sampling and wait for the sampling thread to exit
+    ///
+    /// A callback that is running will be finished first.
+    pub fn stop(self) {}
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Async counterpart of [`Monitor`], created by [`CpuInfo::monitor_async`]
+///
+/// The snapshots are taken on the blocking thread pool and the interval is waited for with [`tokio::time::sleep`].
+/// Use [`into_stream`](AsyncMonitor::into_stream) to consume the readings as a [`Stream`](futures_core::Stream).
+#[cfg(feature = "tokio")]
+pub struct AsyncMonitor {
+    cpu: Arc<CpuInfo>,
+    interval: Duration,
+    last: EnergySnapshot,
+    deadline: tokio::time::Instant,
+}
+
+#[cfg(feature = "tokio")]
+impl CpuInfo {
+    /// Yield the cpu power levels averaged over every `interval` without blocking the async runtime
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// #
+    /// # async fn example() -> Result<(), Error> {
+    ///     let cpu = Arc::new(CpuInfo::new()?);
+    ///     let mut monitor = cpu.monitor_async(Duration::from_secs(1)).await?;
+    ///     loop {
+    ///         let power = monitor.next().await?;
+    ///         println!("{:.2}W", power.packages().sum::<f64>());
+    ///     }
+    /// # }
+    ///```
+    pub async fn monitor_async(
+        self: &Arc<Self>,
+        interval: Duration,
+    ) -> Result<AsyncMonitor, Error> {
+        if interval.is_zero() {
+            return Err(Error::EmptyInterval);
+        }
+        let max = self.max_interval();
+        if interval > max {
+            return Err(Error::IntervalTooLong { interval, max });
+        }
+
+        Ok(AsyncMonitor {
+            cpu: self.clone(),
+            interval,
+            last: self.snapshot_blocking().await?,
+            deadline: tokio::time::Instant::now() + interval,
+        })
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncMonitor {
+    /// The interval between readings
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Wait for the next reading
+    ///
+    /// A failed reading is returned as an error and the next reading continues from the last successful snapshot.
+    pub async fn next(&mut self) -> Result<CpuPower, Error> {
+        tokio::time::sleep_until(self.deadline).await;
+        self.deadline = tokio::time::Instant::now() + self.interval;
+
+        let snapshot = self.cpu.snapshot_blocking().await?;
+        let power = CpuPower::between(&self.last, &snapshot);
+        self.last = snapshot;
+        power
+    }
+
+    /// Turn the monitor into a [`Stream`](futures_core::Stream) of readings
+    ///
+    /// Like the monitor the stream never ends, failed readings are yielded as errors.
+    pub fn into_stream(self) -> MonitorStream {
+        MonitorStream {
+            next: Box::pin(next_owned(self)),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+async fn next_owned(mut monitor: AsyncMonitor) -> (AsyncMonitor, Result<CpuPower, Error>) {
+    let power = monitor.next().await;
+    (monitor, power)
+}
+
+#[cfg(feature = "tokio")]
+type NextReading = std::pin::Pin<
+    Box<dyn std::future::Future<Output = (AsyncMonitor, Result<CpuPower, Error>)> + Send>,
+>;
+
+/// A [`Stream`](futures_core::Stream) of readings, created by [`AsyncMonitor::into_stream`]
+#[cfg(feature = "tokio")]
+pub struct MonitorStream {
+    next: NextReading,
+}
+
+#[cfg(feature = "tokio")]
+impl futures_core::Stream for MonitorStream {
+    type Item = Result<CpuPower, Error>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        let (monitor, power) =
+            futures_core::ready!(std::future::Future::poll(self.next.as_mut(), cx));
+        self.next = Box::pin(next_owned(monitor));
+        std::task::Poll::Ready(Some(power))
+    }
+}
@@ -0,0 +1,161 @@
+//! Package power limits through the kernel powercap interface
+//!
+//! Where the kernel exposes RAPL constraints for the cpu packages under `/sys/class/powercap`
+//! these can be used to read and, with the `limits` feature, set package power limits.
+//! This is a safer way of capping power than writing msr registers directly,
+//! the kernel validates the limits and restores them on reboot.
+
+use crate::sysfs::{read_string, read_value};
+use crate::Error;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+
+const POWERCAP_PATH: &str = "/sys/class/powercap";
+
+/// A powercap zone for a single cpu package
+#[derive(Debug, Clone)]
+pub struct PackageZone {
+    path: PathBuf,
+    package: u32,
+}
+
+/// A power limit constraint of a powercap zone
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    /// Index of the constraint within the zone
+    pub index: u32,
+    /// Name of the constraint, usually `long_term` or `short_term`
+    pub name: String,
+    /// The current power limit in watt
+    pub power_limit: f64,
+    /// The highest power limit that can be set in watt, if reported by the kernel
+    pub max_power: Option<f64>,
+}
+
+/// Find the powercap zones of all cpu packages
+pub fn package_zones() -> Result<Vec<PackageZone>, Error> {
+    let entries = match read_dir(POWERCAP_PATH) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::Powercap(e)),
+    };
+
+    let mut zones: Vec<PackageZone> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = read_string(path.join("name")).ok()?;
+            let package = name.strip_prefix("package-")?.parse().ok()?;
+            Some(PackageZone { path, package })
+        })
+        .collect();
+    zones.sort_by_key(|zone| zone.package);
+    zones.dedup_by_key(|zone| zone.package);
+
+    Ok(zones)
+}
+
+/// Find the powercap zone of a cpu package
+pub fn package_zone(package: u32) -> Result<Option<PackageZone>, Error> {
+    Ok(package_zones()?
+        .into_iter()
+        .find(|zone| zone.package == package))
+}
+
+fn micro_to_unit(value: u64) -> f64 {
+    value as f64 / 1_000_000.0
+}
+
+impl PackageZone {
+    /// A zone at a custom path, for sysfs mounted outside of `/sys`
+    pub fn at<P: Into<PathBuf>>(path: P, package: u32) -> Self {
+        PackageZone {
+            path: path.into(),
+            package,
+        }
+    }
+
+    /// The package this zone belongs to
+    pub fn package(&self) -> u32 {
+        self.package
+    }
+
+    /// The sysfs path of the zone
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get all power limit constraints of the zone
+    pub fn constraints(&self) -> Result<Vec<Constraint>, Error> {
+        let mut constraints = Vec::new();
+
+        for index in 0.. {
+            let prefix = format!("constraint_{}", index);
+            let name = match read_string(self.path.join(format!("{}_name", prefix))) {
+                Ok(name) => name,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => break,
+                Err(e) => return Err(Error::Powercap(e)),
+            };
+            let power_limit = read_value(self.path.join(format!("{}_power_limit_uw", prefix)))
+                .map_err(Error::Powercap)?;
+            let max_power = read_value(self.path.join(format!("{}_max_power_uw", prefix))).ok();
+
+            constraints.push(Constraint {
+                index,
+                name,
+                power_limit: micro_to_unit(power_limit),
+                max_power: max_power.map(micro_to_unit),
+            });
+        }
+
+        Ok(constraints)
+    }
+
+    /// Set the power limit of a constraint in watt
+    ///
+    /// Limits that aren't a positive number are rejected with [`Error::InvalidPowerLimit`], limits above the
+    /// maximum reported by the kernel with [`Error::PowerLimitTooHigh`], in both cases nothing is written.
+    #[cfg(feature = "limits")]
+    pub fn set_power_limit(&self, constraint: u32, watt: f64) -> Result<(), Error> {
+        if !watt.is_finite() || watt <= 0.0 {
+            return Err(Error::InvalidPowerLimit(watt));
+        }
+        let microwatt = (watt * 1_000_000.0).round() as u64;
+        let max: Option<u64> = read_value(
+            self.path
+                .join(format!("constraint_{}_max_power_uw", constraint)),
+        )
+        .ok();
+        if let Some(max) = max.filter(|max| microwatt > *max) {
+            return Err(Error::PowerLimitTooHigh {
+                watt,
+                max: micro_to_unit(max),
+            });
+        }
+        std::fs::write(
+            self.path
+                .join(format!("constraint_{}_power_limit_uw", constraint)),
+            microwatt.to_string(),
+        )
+        .map_err(Error::Powercap)
+    }
+
+    /// Enable or disable power limiting for the zone
+    #[cfg(feature = "limits")]
+    pub fn set_enabled(&self, enabled: bool) -> Result<(), Error> {
+        std::fs::write(self.path.join("enabled"), if enabled { "1" } else { "0" })
+            .map_err(Error::Powercap)
+    }
+}
+
+/// Set the long term power limit of a package in watt
+#[cfg(feature = "limits")]
+pub fn set_package_limit(package: u32, watt: f64) -> Result<(), Error> {
+    let zone = package_zone(package)?.ok_or(Error::NoPowercapZone(package))?;
+    let constraint = zone
+        .constraints()?
+        .into_iter()
+        .find(|constraint| constraint.name == "long_term")
+        .map_or(0, |constraint| constraint.index);
+    zone.set_power_limit(constraint, watt)
+}
@@ -0,0 +1,211 @@
+use crate::sysfs::read_string;
+use crate::{package_values, CpuInfo, Error};
+use std::collections::HashMap;
+use std::fs::read_dir;
+use std::process::{Child, Command, ExitStatus};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const SAMPLE_STEP: Duration = Duration::from_millis(10);
+
+/// Estimated energy usage of a single process
+#[derive(Debug, Clone)]
+pub struct ProcessEnergy {
+    /// Estimated energy used by the process in joule
+    pub energy: f64,
+    /// The duration of the measurement, shorter than the requested interval if the process exited
+    pub duration: Duration,
+    /// The logical cpus the process was seen running on
+    pub cpus: Vec<u32>,
+}
+
+impl ProcessEnergy {
+    /// Average power draw of the process over the measurement in watt
+    pub fn power(&self) -> f64 {
+        self.energy / self.duration.as_secs_f64()
+    }
+}
+
+/// Cpu time in nanoseconds and the last cpu for every thread of a process
+fn task_times(pid: i32) -> Option<HashMap<u32, (u64, u32)>> {
+    let tasks = read_dir(format!("/proc/{}/task", pid)).ok()?;
+    let mut times = HashMap::new();
+
+    for task in tasks.flatten() {
+        let tid: u32 = match task.file_name().to_str().and_then(|tid| tid.parse().ok()) {
+            Some(tid) => tid,
+            None => continue,
+        };
+        let path = task.path();
+        let (schedstat, stat) = match (
+            read_string(path.join("schedstat")),
+            read_string(path.join("stat")),
+        ) {
+            (Ok(schedstat), Ok(stat)) => (schedstat, stat),
+            // the thread exited between listing and reading
+            _ => continue,
+        };
+        let runtime = schedstat
+            .split(' ')
+            .next()
+            .and_then(|time| time.parse().ok());
+        // the comm field can contain spaces, so start counting after it, the cpu is field 39
+        let cpu = stat
+            .rsplit_once(')')
+            .and_then(|(_, fields)| fields.split_whitespace().nth(36))
+            .and_then(|cpu| cpu.parse().ok());
+        if let (Some(runtime), Some(cpu)) = (runtime, cpu) {
+            times.insert(tid, (runtime, cpu));
+        }
+    }
+
+    Some(times)
+}
+
+impl CpuInfo {
+    /// Estimate the energy used by a process over `interval`
+    ///
+    /// The threads of the process are sampled every 10ms to find the cpu they run on and their share of the cpu time,
+    /// the energy of each core is then attributed to the process by that share.
+    /// Because sibling threads and other processes on the same core are not taken into account this is only an estimate.
+    ///
+    /// Requires `/proc/<pid>/task/<tid>/schedstat`, which the kernel provides when scheduler statistics are enabled.
+    ///
+    /// Note that this method will block for `interval` or until the process exits
+    pub fn measure_process(&self, pid: i32, interval: Duration) -> Result<ProcessEnergy, Error> {
+        let mut last_times = task_times(pid).ok_or(Error::ProcessNotFound(pid))?;
+        let mut last_snapshot = self.snapshot()?;
+        let start = Instant::now();
+        let mut last_time = start;
+
+        let mut energy = 0.0;
+        let mut cpus = Vec::new();
+
+        while last_time.duration_since(start) < interval {
+            sleep(SAMPLE_STEP);
+
+            let times = match task_times(pid) {
+                Some(times) => times,
+                None => break,
+            };
+            let snapshot = self.snapshot()?;
+            let (core_energy, _) = last_snapshot.energy_until(&snapshot);
+            let now = Instant::now();
+            let elapsed = now.duration_since(last_time).as_nanos() as f64;
+
+            for (tid, (runtime, cpu)) in &times {
+                let last_runtime = match last_times.get(tid) {
+                    Some((last_runtime, _)) => *last_runtime,
+                    None => continue,
+                };
+                let index = match self.cores.iter().position(|core| core.cpu == *cpu) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let share = (runtime.saturating_sub(last_runtime) as f64 / elapsed).min(1.0);
+                energy += share * core_energy[index].core_power.unwrap_or_default();
+                if share > 0.0 && !cpus.contains(cpu) {
+                    cpus.push(*cpu);
+                }
+            }
+
+            last_times = times;
+            last_snapshot = snapshot;
+            last_time = now;
+        }
+
+        cpus.sort_unstable();
+
+        Ok(ProcessEnergy {
+            energy,
+            duration: last_time.duration_since(start),
+            cpus,
+        })
+    }
+}
+
+/// Kills and reaps the child when dropped, so an error while measuring doesn't leave it running
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        // both are no-ops for a child that already exited and was waited for
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Energy used while running a command
+#[derive(Debug, Clone)]
+pub struct Energy {
+    /// Total package energy used while the command was running in joule
+    pub energy: f64,
+    /// Highest total package power seen while the command was running in watt
+    pub peak: f64,
+    /// Wall time the command was running for
+    pub duration: Duration,
+    /// Exit status of the command
+    pub status: ExitStatus,
+}
+
+impl Energy {
+    /// Run a command to completion and measure the package energy used while it ran
+    ///
+    /// Note that the energy of everything running on the system is measured, not only that of the command.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{process::Energy, Error};
+    /// # use std::process::Command;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let energy = Energy::of(Command::new("make"))?;
+    ///     println!("{:.2}J, peak {:.2}W", energy.energy, energy.peak);
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn of(command: Command) -> Result<Self, Error> {
+        Self::of_with(&CpuInfo::new()?, command)
+    }
+
+    /// Run a command to completion and measure the package energy used while it ran using an existing [`CpuInfo`]
+    ///
+    /// If reading the counters fails the command is killed before the error is returned.
+    pub fn of_with(cpu: &CpuInfo, mut command: Command) -> Result<Self, Error> {
+        let start = Instant::now();
+        let mut last_snapshot = cpu.snapshot()?;
+        let mut child = ChildGuard(command.spawn().map_err(Error::Spawn)?);
+
+        let mut total = 0.0;
+        let mut last_time = start;
+        let mut peak = 0.0f64;
+
+        let status = loop {
+            sleep(SAMPLE_STEP);
+            let status = child.0.try_wait().map_err(Error::Spawn)?;
+
+            let snapshot = cpu.snapshot()?;
+            let now = Instant::now();
+            let (cores, _) = last_snapshot.energy_until(&snapshot);
+            let energy: f64 = package_values(&cores, cpu.package_aggregation)
+                .map(|(_, energy)| energy)
+                .sum();
+            total += energy;
+            peak = peak.max(energy / now.duration_since(last_time).as_secs_f64());
+            last_snapshot = snapshot;
+            last_time = now;
+
+            if let Some(status) = status {
+                break status;
+            }
+        };
+
+        Ok(Energy {
+            energy: total,
+            peak,
+            duration: last_time.duration_since(start),
+            status,
+        })
+    }
+}
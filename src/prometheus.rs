@@ -0,0 +1,346 @@
+//! Prometheus exporter
+//!
+//! Serves the core and package power in the prometheus text format over http.
+//! Every scrape reports the average power since the previous scrape, so no samples are missed between scrapes.
+//!
+//! Besides the metrics the exporter serves a liveness check on `/healthz` and a readiness check on `/readyz`.
+//! The exporter can be started by systemd socket activation with [`Exporter::serve_activated`].
+
+use crate::{package_values, CpuInfo, CpuPower, EnergySnapshot, Error, SCHEMA_VERSION};
+use std::env::{remove_var, var};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// How long a client can take to send its request or receive the response before it is dropped
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The first file descriptor passed by systemd socket activation
+const LISTEN_FDS_START: RawFd = 3;
+
+/// A listening socket passed by systemd socket activation
+#[derive(Debug)]
+pub enum ActivatedListener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+fn socket_option(fd: RawFd, option: libc::c_int) -> Option<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut length = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            option,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    (result == 0).then_some(value)
+}
+
+/// Take the listening socket passed by systemd socket activation
+///
+/// Returns `None` if the process wasn't socket activated, `LISTEN_PID` is for another process or the passed
+/// file descriptor isn't a listening stream socket. Only the first passed socket is used.
+/// The activation variables are removed so they aren't inherited by child processes.
+pub fn activated_listener() -> Option<ActivatedListener> {
+    let pid = var("LISTEN_PID").ok();
+    let fds = var("LISTEN_FDS").ok();
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        remove_var(name);
+    }
+    if pid?.parse::<u32>().ok()? != std::process::id() || fds?.parse::<u32>().ok()? < 1 {
+        return None;
+    }
+
+    let fd = LISTEN_FDS_START;
+    if socket_option(fd, libc::SO_ACCEPTCONN)? != 1
+        || socket_option(fd, libc::SO_TYPE)? != libc::SOCK_STREAM
+    {
+        return None;
+    }
+    let domain = socket_option(fd, libc::SO_DOMAIN)?;
+    // systemd doesn't set close-on-exec on the passed descriptors
+    unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    match domain {
+        libc::AF_INET | libc::AF_INET6 => Some(ActivatedListener::Tcp(unsafe {
+            TcpListener::from_raw_fd(fd)
+        })),
+        libc::AF_UNIX => Some(ActivatedListener::Unix(unsafe {
+            UnixListener::from_raw_fd(fd)
+        })),
+        _ => None,
+    }
+}
+
+/// A connected http client
+trait Client: Sized
+where
+    for<'a> &'a Self: Read + Write,
+{
+    fn set_timeouts(&self, timeout: Duration) -> io::Result<()>;
+}
+
+impl Client for TcpStream {
+    fn set_timeouts(&self, timeout: Duration) -> io::Result<()> {
+        self.set_read_timeout(Some(timeout))?;
+        self.set_write_timeout(Some(timeout))
+    }
+}
+
+impl Client for UnixStream {
+    fn set_timeouts(&self, timeout: Duration) -> io::Result<()> {
+        self.set_read_timeout(Some(timeout))?;
+        self.set_write_timeout(Some(timeout))
+    }
+}
+
+/// Render a reading in the prometheus text format
+///
+/// The [`SCHEMA_VERSION`] of the metrics is reported as `ryzen_schema_version`.
+pub fn render(power: &CpuPower) -> String {
+    let mut output = String::new();
+
+    output.push_str("# HELP ryzen_schema_version Version of the metric names and labels\n");
+    output.push_str("# TYPE ryzen_schema_version gauge\n");
+    let _ = writeln!(output, "ryzen_schema_version {}", SCHEMA_VERSION);
+
+    output.push_str("# HELP ryzen_core_power_watts Power draw of a cpu core\n");
+    output.push_str("# TYPE ryzen_core_power_watts gauge\n");
+    // labelled with the cpu id, positions change with the monitored cpus
+    for reading in &power.cores {
+        if let Some(watt) = reading.core_power {
+            let _ = writeln!(
+                output,
+                "ryzen_core_power_watts{{cpu=\"{}\",package=\"{}\"}} {}",
+                reading.cpu, reading.package, watt
+            );
+        }
+    }
+
+    output.push_str("# HELP ryzen_package_power_watts Power draw of a cpu package\n");
+    output.push_str("# TYPE ryzen_package_power_watts gauge\n");
+    for (package, watt) in package_values(&power.cores, power.package_aggregation) {
+        let _ = writeln!(
+            output,
+            "ryzen_package_power_watts{{package=\"{}\"}} {}",
+            package, watt
+        );
+    }
+
+    output
+}
+
+/// The outcome of the samples taken by the exporter
+#[derive(Debug, Default)]
+struct Health {
+    /// Time of the last successful snapshot
+    last_success: Option<Instant>,
+    errors: u64,
+}
+
+/// A prometheus exporter for a [`CpuInfo`]
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::{CpuInfo, Error};
+/// # use ryzen_reader::prometheus::Exporter;
+/// #
+/// # fn main() -> Result<(), Error> {
+///     Exporter::new(CpuInfo::new()?).serve("0.0.0.0:9835")?;
+/// #     Ok(())
+/// # }
+///```
+///
+/// Cpus brought online while the exporter is running are picked up with [`CpuInfo::rescan`].
+///
+/// # Health checks
+///
+/// `/healthz` always answers with status 200 while the exporter is serving, since requests are handled one at a time
+/// a wedged reader makes the check time out. `/readyz` reads the counters and answers with status 503 if that fails.
+/// Both report the counter source, the age of the last successful sample in seconds and the number of failed samples.
+pub struct Exporter {
+    cpu: RwLock<CpuInfo>,
+    last: Mutex<Option<EnergySnapshot>>,
+    health: Mutex<Health>,
+}
+
+impl Exporter {
+    pub fn new(cpu: CpuInfo) -> Self {
+        Exporter {
+            cpu: RwLock::new(cpu),
+            last: Mutex::new(None),
+            health: Mutex::new(Health::default()),
+        }
+    }
+
+    /// Take a snapshot and record the outcome for the health checks
+    fn snapshot(&self, cpu: &CpuInfo) -> Result<EnergySnapshot, Error> {
+        let snapshot = cpu.snapshot();
+        let mut health = self.health.lock().unwrap();
+        match &snapshot {
+            Ok(snapshot) => health.last_success = Some(snapshot.time()),
+            Err(_) => health.errors += 1,
+        }
+        snapshot
+    }
+
+    /// The body of the health checks
+    fn health_report(&self, cpu: &CpuInfo, status: &str) -> String {
+        let health = self.health.lock().unwrap();
+        let age = health.last_success.map_or_else(
+            || "-".to_string(),
+            |time| {
+                cpu.clock
+                    .now()
+                    .saturating_duration_since(time)
+                    .as_secs_f64()
+                    .to_string()
+            },
+        );
+        format!(
+            "status {}\nsource {}\nlast_sample_age_seconds {}\nerrors {}\n",
+            status,
+            cpu.source(),
+            age,
+            health.errors
+        )
+    }
+
+    /// The liveness check, answered as long as the exporter is serving
+    pub fn healthz(&self) -> String {
+        self.health_report(&self.cpu.read().unwrap(), "ok")
+    }
+
+    /// The readiness check, reads the counters and fails if that isn't possible
+    ///
+    /// The report is returned both when the check succeeds and when it fails.
+    pub fn readyz(&self) -> Result<String, String> {
+        let cpu = self.cpu.read().unwrap();
+        match self.snapshot(&cpu) {
+            Ok(_) => Ok(self.health_report(&cpu, "ok")),
+            Err(e) => Err(self.health_report(&cpu, &format!("error {}", e))),
+        }
+    }
+
+    /// Get the metrics for the interval since the previous call
+    ///
+    /// The first call takes a regular blocking reading.
+    pub fn metrics(&self) -> Result<String, Error> {
+        let mut last = self.last.lock().unwrap();
+        if self.cpu.read().unwrap().rescan_needed() {
+            self.cpu.write().unwrap().rescan()?;
+        }
+        let cpu = self.cpu.read().unwrap();
+        let snapshot = self.snapshot(&cpu)?;
+        let power = match last.as_ref() {
+            Some(last) => match CpuPower::between(last, &snapshot) {
+                Ok(power) => power,
+                // scrapes too far apart or too close together, fall back to a fresh reading
+                Err(Error::IntervalTooLong { .. }) | Err(Error::EmptyInterval) => cpu.read()?,
+                Err(e) => return Err(e),
+            },
+            None => cpu.read()?,
+        };
+        *last = Some(snapshot);
+        Ok(render(&power))
+    }
+
+    /// Listen on `address` and serve the metrics to every http request
+    ///
+    /// Note that this method blocks forever unless accepting connections fails
+    pub fn serve<A: ToSocketAddrs>(&self, address: A) -> Result<(), Error> {
+        self.serve_listener(TcpListener::bind(address).map_err(Error::IO)?)
+    }
+
+    /// Serve the metrics to every http request on an already bound listener
+    ///
+    /// Note that this method blocks forever unless accepting connections fails
+    pub fn serve_listener(&self, listener: TcpListener) -> Result<(), Error> {
+        self.serve_clients(listener.incoming())
+    }
+
+    /// Serve the metrics to every http request on a listening unix socket
+    ///
+    /// Note that this method blocks forever unless accepting connections fails
+    pub fn serve_unix_listener(&self, listener: UnixListener) -> Result<(), Error> {
+        self.serve_clients(listener.incoming())
+    }
+
+    /// Serve on the socket passed by systemd socket activation, or listen on `fallback` when not socket activated
+    ///
+    /// Both tcp and unix sockets can be passed, see [`activated_listener`].
+    ///
+    /// Note that this method blocks forever unless accepting connections fails
+    pub fn serve_activated<A: ToSocketAddrs>(&self, fallback: A) -> Result<(), Error> {
+        match activated_listener() {
+            Some(ActivatedListener::Tcp(listener)) => self.serve_listener(listener),
+            Some(ActivatedListener::Unix(listener)) => self.serve_unix_listener(listener),
+            None => self.serve(fallback),
+        }
+    }
+
+    fn serve_clients<C, I>(&self, clients: I) -> Result<(), Error>
+    where
+        C: Client,
+        for<'a> &'a C: Read + Write,
+        I: Iterator<Item = io::Result<C>>,
+    {
+        for stream in clients {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::IO(e)),
+            };
+            // a misbehaving or timed out client is dropped and doesn't stop the exporter
+            let _ = self.respond(stream);
+        }
+        Ok(())
+    }
+
+    fn respond<C>(&self, stream: C) -> io::Result<()>
+    where
+        C: Client,
+        for<'a> &'a C: Read + Write,
+    {
+        // clients are handled one at a time, a silent client shouldn't block later scrapes
+        stream.set_timeouts(CLIENT_TIMEOUT)?;
+        let mut reader = BufReader::new(&stream);
+        let mut request = String::new();
+        reader.read_line(&mut request)?;
+        // drain the headers
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 2 {
+            line.clear();
+        }
+
+        let path = request.split(' ').nth(1).unwrap_or("/");
+        let (status, content_type, body) = match path {
+            "/healthz" => ("200 OK", "text/plain", self.healthz()),
+            "/readyz" => match self.readyz() {
+                Ok(body) => ("200 OK", "text/plain", body),
+                Err(body) => ("503 Service Unavailable", "text/plain", body),
+            },
+            _ => match self.metrics() {
+                Ok(body) => ("200 OK", "text/plain; version=0.0.4", body),
+                Err(e) => ("500 Internal Server Error", "text/plain", e.to_string()),
+            },
+        };
+        let mut stream = &stream;
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )
+    }
+}
@@ -0,0 +1,67 @@
+//! [uom](https://docs.rs/uom) quantity accessors for all measured values, enabled with the `uom` feature
+
+use crate::fancontrol::FanControlEvent;
+use crate::process::{Energy as CommandEnergy, ProcessEnergy};
+use crate::scope::ScopeEnergy;
+use crate::{AccumulatedEnergy, CpuPower};
+use uom::si::energy::joule;
+use uom::si::f64::{Energy, Power, ThermodynamicTemperature};
+use uom::si::power::watt;
+use uom::si::thermodynamic_temperature::degree_celsius;
+
+impl CpuPower {
+    /// Get an iterator for all cpu cores in the system and their power draw
+    pub fn cores_uom(&self) -> impl Iterator<Item = Power> + '_ {
+        self.cores().map(Power::new::<watt>)
+    }
+
+    /// Get an iterator for all cpu packages in the system and their power draw
+    pub fn packages_uom(&self) -> impl Iterator<Item = Power> + '_ {
+        self.packages().map(Power::new::<watt>)
+    }
+}
+
+impl AccumulatedEnergy {
+    /// Get an iterator for all cpu cores in the system and their accumulated energy
+    pub fn cores_uom(&self) -> impl Iterator<Item = Energy> + '_ {
+        self.cores().map(Energy::new::<joule>)
+    }
+
+    /// Get an iterator for all cpu packages in the system and their accumulated energy
+    pub fn packages_uom(&self) -> impl Iterator<Item = Energy> + '_ {
+        self.packages().map(Energy::new::<joule>)
+    }
+}
+
+impl ProcessEnergy {
+    pub fn energy_uom(&self) -> Energy {
+        Energy::new::<joule>(self.energy)
+    }
+
+    pub fn power_uom(&self) -> Power {
+        Power::new::<watt>(self.power())
+    }
+}
+
+impl CommandEnergy {
+    pub fn energy_uom(&self) -> Energy {
+        Energy::new::<joule>(self.energy)
+    }
+
+    pub fn peak_uom(&self) -> Power {
+        Power::new::<watt>(self.peak)
+    }
+}
+
+impl ScopeEnergy {
+    pub fn energy_uom(&self) -> Energy {
+        Energy::new::<joule>(self.energy)
+    }
+}
+
+impl FanControlEvent {
+    pub fn temperature_uom(&self) -> Option<ThermodynamicTemperature> {
+        self.temperature
+            .map(ThermodynamicTemperature::new::<degree_celsius>)
+    }
+}
@@ -0,0 +1,106 @@
+//! Combine tokio runtime busy time with package power
+//!
+//! Relating how busy the runtime workers were to the package energy used in the same window
+//! allows estimating the energy cost of the work done by an async service.
+
+use crate::{CpuInfo, Error};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Handle, RuntimeMetrics};
+
+/// Runtime busy time and package energy over a single window
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct RuntimeSample {
+    /// The length of the window
+    pub elapsed: Duration,
+    /// Total time the runtime workers were busy during the window, summed over all workers
+    pub busy: Duration,
+    /// Fraction of the available worker time that was busy
+    pub utilization: f64,
+    /// Total package energy used during the window in joule
+    pub energy: f64,
+}
+
+impl RuntimeSample {
+    /// Average total package power during the window in watt
+    pub fn power(&self) -> f64 {
+        self.energy / self.elapsed.as_secs_f64()
+    }
+
+    /// Package energy used per second of worker busy time in joule, `None` if the runtime wasn't busy
+    pub fn energy_per_busy_second(&self) -> Option<f64> {
+        let busy = self.busy.as_secs_f64();
+        (busy > 0.0).then(|| self.energy / busy)
+    }
+}
+
+/// Samples tokio runtime metrics together with the package energy
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::{CpuInfo, Error};
+/// # use ryzen_reader::runtime::RuntimeEnergy;
+/// #
+/// # fn main() -> Result<(), Error> {
+/// #     let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+///     let mut sampler = RuntimeEnergy::new(runtime.handle(), CpuInfo::new()?)?;
+///     // ...
+///     let sample = sampler.sample()?;
+///     println!("{:.0}% busy, {:.2}W", sample.utilization * 100.0, sample.power());
+/// #     Ok(())
+/// # }
+///```
+pub struct RuntimeEnergy {
+    metrics: RuntimeMetrics,
+    cpu: CpuInfo,
+    last_busy: Duration,
+    last_energy: f64,
+    last_time: Instant,
+}
+
+fn total_busy(metrics: &RuntimeMetrics) -> Duration {
+    (0..metrics.num_workers())
+        .map(|worker| metrics.worker_total_busy_duration(worker))
+        .sum()
+}
+
+impl RuntimeEnergy {
+    pub fn new(handle: &Handle, cpu: CpuInfo) -> Result<Self, Error> {
+        let metrics = handle.metrics();
+        let last_energy = cpu.energy_since_init()?.packages().sum();
+        Ok(RuntimeEnergy {
+            last_busy: total_busy(&metrics),
+            metrics,
+            cpu,
+            last_energy,
+            last_time: Instant::now(),
+        })
+    }
+
+    /// Get the busy time and energy since the previous sample, or since creation for the first sample
+    pub fn sample(&mut self) -> Result<RuntimeSample, Error> {
+        let energy: f64 = self.cpu.energy_since_init()?.packages().sum();
+        let busy = total_busy(&self.metrics);
+        let now = Instant::now();
+
+        let elapsed = now.duration_since(self.last_time);
+        let busy_delta = busy.saturating_sub(self.last_busy);
+        let available = elapsed.as_secs_f64() * self.metrics.num_workers() as f64;
+        let sample = RuntimeSample {
+            elapsed,
+            busy: busy_delta,
+            utilization: if available > 0.0 {
+                busy_delta.as_secs_f64() / available
+            } else {
+                0.0
+            },
+            energy: energy - self.last_energy,
+        };
+
+        self.last_busy = busy;
+        self.last_energy = energy;
+        self.last_time = now;
+        Ok(sample)
+    }
+}
@@ -0,0 +1,39 @@
+//! Versioning of the serialized outputs
+
+/// The version of the schema of all serialized outputs
+///
+/// The schema covers the serde representation of [`CpuPower`](crate::CpuPower),
+/// [`AccumulatedEnergy`](crate::AccumulatedEnergy), [`PackageTemperatures`](crate::thermal::PackageTemperatures)
+/// and [`SmuLimits`](crate::smu::SmuLimits), the `sensors -j` style json and the prometheus metrics.
+/// Each of them reports the version in a `schema_version` field, metric or chip entry.
+///
+/// Within a version fields are only added, never renamed, removed or changed in meaning, so parsers ignoring unknown
+/// fields keep working. Any other change increases the version. Serialized values without a version are from before
+/// the versioning and are read as version 1, values with a newer version than this one are rejected.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The `schema_version` field of the serialized outputs, always serialized as [`SCHEMA_VERSION`]
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct SchemaVersion;
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SchemaVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(SCHEMA_VERSION)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SchemaVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let version = u32::deserialize(deserializer)?;
+        if version > SCHEMA_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "unsupported schema version {}, only versions up to {} are supported",
+                version, SCHEMA_VERSION
+            )));
+        }
+        Ok(SchemaVersion)
+    }
+}
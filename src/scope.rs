@@ -0,0 +1,114 @@
+use crate::{CpuInfo, Error};
+use std::time::{Duration, Instant};
+
+/// Measures the package energy used while the guard is alive
+///
+/// If the guard is dropped without calling [`finish`](EnergyGuard::finish), the energy is reported
+/// as a `tracing` event when the `tracing` feature is enabled. Without the feature nothing is reported,
+/// use [`finish`](EnergyGuard::finish) to get the energy.
+///
+/// See also [`energy_scope!`](crate::energy_scope).
+pub struct EnergyGuard<'a> {
+    cpu: &'a CpuInfo,
+    name: &'static str,
+    start_energy: f64,
+    start: Instant,
+    finished: bool,
+}
+
+/// Energy used within a scope
+#[derive(Debug, Clone, Copy)]
+pub struct ScopeEnergy {
+    /// Total package energy in joule
+    pub energy: f64,
+    pub duration: Duration,
+}
+
+impl<'a> EnergyGuard<'a> {
+    pub fn new(cpu: &'a CpuInfo, name: &'static str) -> Result<Self, Error> {
+        Ok(EnergyGuard {
+            cpu,
+            name,
+            start_energy: cpu.energy_since_init()?.packages().sum(),
+            start: Instant::now(),
+            finished: false,
+        })
+    }
+
+    /// The name the energy is reported under
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn measure(&self) -> Result<ScopeEnergy, Error> {
+        let energy: f64 = self.cpu.energy_since_init()?.packages().sum();
+        Ok(ScopeEnergy {
+            energy: energy - self.start_energy,
+            duration: self.start.elapsed(),
+        })
+    }
+
+    /// Stop measuring and return the energy used instead of reporting it
+    pub fn finish(mut self) -> Result<ScopeEnergy, Error> {
+        self.finished = true;
+        self.measure()
+    }
+}
+
+impl EnergyGuard<'_> {
+    #[cfg(feature = "tracing")]
+    fn report(&self) {
+        match self.measure() {
+            Ok(energy) => tracing::info!(
+                scope = self.name,
+                joules = energy.energy,
+                duration = ?energy.duration,
+                "energy scope finished"
+            ),
+            Err(e) => {
+                tracing::warn!(scope = self.name, error = %e, "failed to measure energy scope")
+            }
+        }
+    }
+
+    /// Nothing is reported without `tracing`
+    #[cfg(not(feature = "tracing"))]
+    fn report(&self) {}
+}
+
+impl Drop for EnergyGuard<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.report();
+        }
+    }
+}
+
+/// Measure the package energy used until the end of the current scope
+///
+/// The energy is reported as a `tracing` event when the scope ends, see [`EnergyGuard`].
+/// Without a name the module path is used.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::{energy_scope, CpuInfo, Error};
+/// #
+/// # fn main() -> Result<(), Error> {
+///     let cpu = CpuInfo::new()?;
+///     {
+///         energy_scope!(cpu, "expensive work");
+///         // ...
+///     }
+/// #     Ok(())
+/// # }
+///```
+#[macro_export]
+macro_rules! energy_scope {
+    ($cpu:expr) => {
+        $crate::energy_scope!($cpu, module_path!())
+    };
+    ($cpu:expr, $name:expr) => {
+        let _energy_guard = $crate::scope::EnergyGuard::new(&$cpu, $name).ok();
+    };
+}
@@ -0,0 +1,83 @@
+//! Output in the formats of the lm-sensors `sensors` tool
+//!
+//! Allows dashboards and scripts that already parse `sensors` or `sensors -j` output to use
+//! the readings of this crate without changes to their parsers.
+//! The json output reports the [`SCHEMA_VERSION`](crate::SCHEMA_VERSION) as a `schema_version` entry of the chip,
+//! next to the adapter.
+
+use crate::{package_values, CpuPower, SCHEMA_VERSION};
+use std::fmt;
+
+/// The chip name the readings are reported under
+const CHIP_NAME: &str = "ryzen_reader-isa-0000";
+const ADAPTER: &str = "ISA adapter";
+
+/// All sensors of a reading as (label, watt)
+fn sensors(power: &CpuPower) -> impl Iterator<Item = (String, f64)> + '_ {
+    let packages = package_values(&power.cores, power.package_aggregation)
+        .map(|(package, power)| (format!("package{}", package), power));
+    // labelled with the cpu id, the position changes when the set of monitored cpus does
+    let cores = power
+        .core_readings()
+        .map(|reading| (format!("core{}", reading.cpu), reading.power));
+    packages.chain(cores)
+}
+
+/// A reading formatted like the output of `sensors`, created with [`CpuPower::sensors`]
+pub struct SensorsText<'a>(&'a CpuPower);
+
+impl fmt::Display for SensorsText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", CHIP_NAME)?;
+        writeln!(f, "Adapter: {}", ADAPTER)?;
+        for (label, power) in sensors(self.0) {
+            writeln!(f, "{:<13} {:>7.2} W", format!("{}:", label), power)?;
+        }
+        Ok(())
+    }
+}
+
+/// A reading formatted like the output of `sensors -j`, created with [`CpuPower::sensors_json`]
+pub struct SensorsJson<'a>(&'a CpuPower);
+
+impl fmt::Display for SensorsJson<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{{")?;
+        writeln!(f, "   \"{}\":{{", CHIP_NAME)?;
+        writeln!(f, "      \"Adapter\": \"{}\",", ADAPTER)?;
+        write!(f, "      \"schema_version\": {}", SCHEMA_VERSION)?;
+        for (index, (label, power)) in sensors(self.0).enumerate() {
+            writeln!(f, ",")?;
+            writeln!(f, "      \"{}\":{{", label)?;
+            writeln!(f, "         \"power{}_input\": {:.3}", index + 1, power)?;
+            write!(f, "      }}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "   }}")?;
+        writeln!(f, "}}")
+    }
+}
+
+impl CpuPower {
+    /// Format the reading like the output of the `sensors` tool
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let power = CpuInfo::new()?.read()?;
+    ///     print!("{}", power.sensors());
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn sensors(&self) -> SensorsText<'_> {
+        SensorsText(self)
+    }
+
+    /// Format the reading like the json output of `sensors -j`
+    pub fn sensors_json(&self) -> SensorsJson<'_> {
+        SensorsJson(self)
+    }
+}
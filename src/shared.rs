@@ -0,0 +1,308 @@
+//! Process wide shared sampler
+//!
+//! When multiple components within one process each own a [`CpuInfo`] they each read every msr,
+//! the shared sampler lets them use a single `CpuInfo` and deduplicates reads that happen close together.
+
+use crate::{CpuInfo, CpuPower, EnergySnapshot, Error};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_FRESHNESS: Duration = Duration::from_millis(100);
+
+/// How much later than expected a sampler snapshot can arrive before a subscriber reads the counters itself
+const SAMPLER_SLACK: Duration = Duration::from_millis(50);
+
+static GLOBAL: Mutex<Option<Arc<SharedSampler>>> = Mutex::new(None);
+
+struct SamplerState {
+    freshness: Duration,
+    last: Option<(Instant, CpuPower)>,
+    /// Whether a [`SharedSampler::read`] is in progress
+    reading: bool,
+    /// The latest snapshot taken by the sampler thread
+    latest: Option<EnergySnapshot>,
+    /// The id and interval of every subscription
+    subscribers: Vec<(u64, Duration)>,
+    next_id: u64,
+    /// Whether the sampler thread is running
+    sampling: bool,
+}
+
+impl SamplerState {
+    /// The sampler runs at the rate of the fastest subscriber, `None` without subscribers
+    fn sample_interval(&self) -> Option<Duration> {
+        self.subscribers.iter().map(|(_, interval)| *interval).min()
+    }
+}
+
+/// A sampler that can be shared between multiple consumers
+///
+/// Reads within the freshness window of the previous read return the previous result,
+/// concurrent reads wait for the read in progress instead of starting their own.
+pub struct SharedSampler {
+    cpu: CpuInfo,
+    state: Mutex<SamplerState>,
+    /// Signalled when a read finished or the sampler took a snapshot
+    updated: Condvar,
+    /// Signalled when the subscribers changed
+    subscribers_changed: Condvar,
+}
+
+impl SharedSampler {
+    pub fn new(cpu: CpuInfo, freshness: Duration) -> Self {
+        SharedSampler {
+            cpu,
+            state: Mutex::new(SamplerState {
+                freshness,
+                last: None,
+                reading: false,
+                latest: None,
+                subscribers: Vec::new(),
+                next_id: 0,
+                sampling: false,
+            }),
+            updated: Condvar::new(),
+            subscribers_changed: Condvar::new(),
+        }
+    }
+
+    /// Get the process wide sampler, creating it on first use with a freshness window of 100ms
+    pub fn global() -> Result<Arc<SharedSampler>, Error> {
+        let mut global = GLOBAL.lock().unwrap();
+        match global.as_ref() {
+            Some(sampler) => Ok(sampler.clone()),
+            None => {
+                let sampler = Arc::new(SharedSampler::new(CpuInfo::new()?, DEFAULT_FRESHNESS));
+                *global = Some(sampler.clone());
+                Ok(sampler)
+            }
+        }
+    }
+
+    /// Change how long a reading is reused for
+    pub fn set_freshness(&self, freshness: Duration) {
+        self.state.lock().unwrap().freshness = freshness;
+    }
+
+    /// Read the cpu power levels, or return the previous reading if it is still fresh
+    ///
+    /// Note that this method will block for the duration of [`CpuInfo::read`] when a new reading is taken
+    pub fn read(&self) -> Result<CpuPower, Error> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some((time, power)) = &state.last {
+                if time.elapsed() < state.freshness {
+                    return Ok(power.clone());
+                }
+            }
+            if !state.reading {
+                break;
+            }
+            state = self.updated.wait(state).unwrap();
+        }
+        state.reading = true;
+        drop(state);
+
+        let power = self.cpu.read();
+        let mut state = self.state.lock().unwrap();
+        state.reading = false;
+        if let Ok(power) = &power {
+            state.last = Some((Instant::now(), power.clone()));
+        }
+        self.updated.notify_all();
+        power
+    }
+
+    /// Take a snapshot of the energy counters, or return the latest snapshot if it is still fresh
+    ///
+    /// Snapshots are shared with the subscriptions, a snapshot taken by the sampler thread is reused
+    /// and a new snapshot can be used by the subscriptions.
+    pub fn snapshot(&self) -> Result<EnergySnapshot, Error> {
+        let state = self.state.lock().unwrap();
+        if let Some(latest) = &state.latest {
+            if self
+                .cpu
+                .clock
+                .now()
+                .saturating_duration_since(latest.time())
+                < state.freshness
+            {
+                return Ok(latest.clone());
+            }
+        }
+        drop(state);
+
+        let snapshot = self.cpu.snapshot()?;
+        let mut state = self.state.lock().unwrap();
+        if state
+            .latest
+            .as_ref()
+            .is_none_or(|latest| latest.time() < snapshot.time())
+        {
+            state.latest = Some(snapshot.clone());
+            self.updated.notify_all();
+        }
+        Ok(snapshot)
+    }
+
+    /// Subscribe to readings averaged over `interval`
+    ///
+    /// Every subscriber can use its own interval. A single background thread reads the counters at the rate
+    /// of the fastest subscriber, every subscriber averages the power between the sampler snapshots at the
+    /// start and end of its own interval. The thread stops when the last subscription is dropped.
+    ///
+    /// A zero interval is rejected with [`Error::EmptyInterval`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{shared::SharedSampler, Error};
+    /// # use std::time::Duration;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let mut logger = SharedSampler::global()?.subscribe(Duration::from_secs(1))?;
+    ///     loop {
+    ///         println!("{:.2}W", logger.read()?.packages().sum::<f64>());
+    ///     }
+    /// # }
+    ///```
+    pub fn subscribe(self: &Arc<Self>, interval: Duration) -> Result<Subscription, Error> {
+        if interval.is_zero() {
+            return Err(Error::EmptyInterval);
+        }
+        let max = self.cpu.max_interval();
+        if interval > max {
+            return Err(Error::IntervalTooLong { interval, max });
+        }
+
+        let mut state = self.state.lock().unwrap();
+        // the last snapshot is only recent while the sampler is running
+        let last = match (&state.latest, state.sampling) {
+            (Some(latest), true) => latest.clone(),
+            _ => {
+                let snapshot = self.cpu.snapshot()?;
+                state.latest = Some(snapshot.clone());
+                snapshot
+            }
+        };
+        if !state.sampling {
+            let sampler = self.clone();
+            // the thread waits for the lock, it sees the new subscriber once it is pushed
+            thread::Builder::new()
+                .name("ryzen-reader-sampler".into())
+                .spawn(move || sampler.sample())
+                .map_err(Error::Spawn)?;
+            state.sampling = true;
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.subscribers.push((id, interval));
+        self.subscribers_changed.notify_all();
+
+        Ok(Subscription {
+            sampler: self.clone(),
+            id,
+            interval,
+            last,
+        })
+    }
+
+    /// Take snapshots at the interval of the fastest subscriber until there are no subscribers left
+    fn sample(&self) {
+        let mut state = self.state.lock().unwrap();
+        let mut last = Instant::now();
+        while let Some(interval) = state.sample_interval() {
+            let now = Instant::now();
+            let due = last + interval;
+            if now < due {
+                // woken early when a faster subscriber joins or the last one leaves
+                state = self
+                    .subscribers_changed
+                    .wait_timeout(state, due - now)
+                    .unwrap()
+                    .0;
+                continue;
+            }
+            drop(state);
+
+            last = Instant::now();
+            // failed snapshots are surfaced by the subscribers reading the counters themselves
+            let snapshot = self.cpu.snapshot().ok();
+            state = self.state.lock().unwrap();
+            if snapshot.is_some() {
+                state.latest = snapshot;
+            }
+            self.updated.notify_all();
+        }
+        state.sampling = false;
+    }
+
+    fn unsubscribe(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .subscribers
+            .retain(|(subscriber, _)| *subscriber != id);
+        self.subscribers_changed.notify_all();
+    }
+
+    /// Wait for the first sampler snapshot taken at or after `due`
+    ///
+    /// Falls back to reading the counters directly when the sampler doesn't deliver in time,
+    /// which also reports the error if the sampler keeps failing.
+    fn snapshot_after(&self, due: Instant, interval: Duration) -> Result<EnergySnapshot, Error> {
+        let deadline = due + interval + SAMPLER_SLACK;
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(latest) = &state.latest {
+                if latest.time() >= due {
+                    return Ok(latest.clone());
+                }
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                drop(state);
+                return self.cpu.snapshot();
+            }
+            state = self.updated.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// The shared `CpuInfo`, for functionality not covered by the sampler
+    pub fn cpu(&self) -> &CpuInfo {
+        &self.cpu
+    }
+}
+
+/// A subscriber of a [`SharedSampler`] with its own interval, created with [`SharedSampler::subscribe`]
+///
+/// Dropping the subscription unsubscribes it from the sampler.
+pub struct Subscription {
+    sampler: Arc<SharedSampler>,
+    id: u64,
+    interval: Duration,
+    last: EnergySnapshot,
+}
+
+impl Subscription {
+    /// Get the power levels averaged over the next interval of this subscriber
+    ///
+    /// Note that this method will block until the interval since the previous reading has passed
+    pub fn read(&mut self) -> Result<CpuPower, Error> {
+        let due = self.last.time() + self.interval;
+        let snapshot = self.sampler.snapshot_after(due, self.interval)?;
+        let power = CpuPower::between(&self.last, &snapshot)?;
+        self.last = snapshot;
+        Ok(power)
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.sampler.unsubscribe(self.id);
+    }
+}
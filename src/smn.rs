@@ -0,0 +1,170 @@
+//! Ccd temperatures read through the system management network
+//!
+//! The thermal registers of the cpu are read over the system management network,
+//! which gives the temperature of every ccd even on kernels where k10temp doesn't expose them yet.
+//!
+//! The registers are read through the `smn` file of the out of tree `ryzen_smu` driver, which performs the
+//! index/data access in the kernel. Accessing the index/data pair in the pci config space of the root complex
+//! directly from userspace races with the kernel drivers using it, that is only available through
+//! the `unsafe` [`SmnSensors::open_pci_config`].
+//!
+//! Requires root. Only the ccds of the first package are read.
+
+use crate::support::current_cpu;
+use crate::Error;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::sync::Mutex;
+
+/// The smn access of the `ryzen_smu` driver, an address is written to it after which the register can be read
+const RYZEN_SMU_SMN: &str = "/sys/kernel/ryzen_smu_drv/smn";
+/// The pci config space of the root complex of the first package
+const ROOT_COMPLEX_CONFIG: &str = "/sys/bus/pci/devices/0000:00:00.0/config";
+const SMN_INDEX: u64 = 0x60;
+const SMN_DATA: u64 = 0x64;
+
+/// Base address of the reported temperature control registers
+const THM_BASE: u32 = 0x0005_9800;
+const CCD_TEMP_VALID: u32 = 1 << 11;
+const CCD_TEMP_MASK: u32 = 0x7FF;
+
+/// The offset of the first ccd temperature register from [`THM_BASE`] and the number of ccd registers
+fn ccd_layout(family: u32, model: u32) -> Option<(u32, u32)> {
+    Some(match (family, model) {
+        // Zen, Zen+ and their apus
+        (0x17, 0x01 | 0x08 | 0x11 | 0x18) => (0x154, 4),
+        // Zen 2
+        (0x17, 0x31 | 0x60 | 0x68 | 0x71) => (0x154, 8),
+        // Zen 3
+        (0x19, 0x00..=0x01 | 0x21 | 0x50..=0x5F) => (0x154, 8),
+        // Zen 3+
+        (0x19, 0x40..=0x4F) => (0x300, 8),
+        // Zen 4 desktop and mobile
+        (0x19, 0x60..=0x7F) => (0x308, 8),
+        // Zen 4 server
+        (0x19, 0x10..=0x1F | 0xA0..=0xAF) => (0x300, 12),
+        _ => return None,
+    })
+}
+
+/// The temperatures of the ccds of the first package
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct CcdTemps {
+    /// The ccd index and its temperature in degrees celsius for every ccd present, ordered by ccd
+    pub temperatures: Vec<(u32, f64)>,
+}
+
+/// Reader for the ccd temperature registers
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::{Error, smn::SmnSensors};
+/// #
+/// # fn main() -> Result<(), Error> {
+///     let sensors = SmnSensors::open()?;
+///     for (ccd, temperature) in sensors.read()?.temperatures {
+///         println!("Tccd{}: {:.1}C", ccd + 1, temperature);
+///     }
+/// #     Ok(())
+/// # }
+///```
+#[derive(Debug)]
+pub struct SmnSensors {
+    access: Mutex<SmnAccess>,
+    ccd_offset: u32,
+    ccds: Vec<u32>,
+}
+
+#[derive(Debug)]
+enum SmnAccess {
+    /// The `smn` file of `ryzen_smu`
+    RyzenSmu(File),
+    /// The index/data pair in the root complex config space
+    PciConfig(File),
+}
+
+fn open_read_write(path: &str) -> Result<File, Error> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(Error::Smn)
+}
+
+impl SmnSensors {
+    /// Open the smn interface of the `ryzen_smu` driver and detect which ccds are present
+    pub fn open() -> Result<Self, Error> {
+        Self::with_access(SmnAccess::RyzenSmu(open_read_write(RYZEN_SMU_SMN)?))
+    }
+
+    /// Access the smn index/data pair in the pci config space of the root complex directly,
+    /// for systems without the `ryzen_smu` driver
+    ///
+    /// # Safety
+    ///
+    /// The kernel (`amd_nb`, `k10temp` and others) accesses the same index/data pair under a lock that isn't
+    /// available to userspace. An access interleaved with the kernel can make a read return an unrelated register
+    /// and can make the kernel read or write the wrong smn address.
+    /// The caller has to make sure no kernel driver accesses the smn while the sensors are used,
+    /// for example by unloading `k10temp`.
+    pub unsafe fn open_pci_config() -> Result<Self, Error> {
+        Self::with_access(SmnAccess::PciConfig(open_read_write(ROOT_COMPLEX_CONFIG)?))
+    }
+
+    fn with_access(access: SmnAccess) -> Result<Self, Error> {
+        let (family, model) = current_cpu().ok_or(Error::UnsupportedPlatform)?;
+        let (ccd_offset, count) =
+            ccd_layout(family, model).ok_or(Error::UnsupportedCpu { family, model })?;
+
+        let mut sensors = SmnSensors {
+            access: Mutex::new(access),
+            ccd_offset,
+            ccds: Vec::new(),
+        };
+        for ccd in 0..count {
+            if sensors.read_ccd(ccd)? & CCD_TEMP_VALID != 0 {
+                sensors.ccds.push(ccd);
+            }
+        }
+        Ok(sensors)
+    }
+
+    fn read_register(&self, address: u32) -> Result<u32, Error> {
+        let access = self.access.lock().unwrap();
+        let (file, index, data) = match &*access {
+            SmnAccess::RyzenSmu(file) => (file, 0, 0),
+            SmnAccess::PciConfig(file) => (file, SMN_INDEX, SMN_DATA),
+        };
+        file.write_all_at(&address.to_le_bytes(), index)
+            .map_err(Error::Smn)?;
+        let mut value = [0; 4];
+        file.read_exact_at(&mut value, data).map_err(Error::Smn)?;
+        Ok(u32::from_le_bytes(value))
+    }
+
+    fn read_ccd(&self, ccd: u32) -> Result<u32, Error> {
+        self.read_register(THM_BASE + self.ccd_offset + ccd * 4)
+    }
+
+    /// The indexes of the ccds that are present
+    pub fn ccds(&self) -> &[u32] {
+        &self.ccds
+    }
+
+    /// Read the temperature of every ccd
+    pub fn read(&self) -> Result<CcdTemps, Error> {
+        let temperatures = self
+            .ccds
+            .iter()
+            .map(|&ccd| {
+                let value = self.read_ccd(ccd)? & CCD_TEMP_MASK;
+                // in 1/8 degree steps with a -49 degree offset
+                Ok((ccd, value as f64 * 0.125 - 49.0))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(CcdTemps { temperatures })
+    }
+}
@@ -0,0 +1,128 @@
+//! Package power, current and electrical limits from the smu telemetry
+//!
+//! The smu keeps a table of power management telemetry, including the PPT (socket power), TDC (sustained current)
+//! and EDC (peak current) limits and their current values as used by the boost algorithm.
+//! The table is exposed by the out of tree `ryzen_smu` driver, reading it requires root.
+//!
+//! The layout of the table differs between cpus, only the Zen 2 and Zen 3 desktop and workstation layouts are supported.
+
+#[cfg(feature = "serde")]
+use crate::schema::SchemaVersion;
+use crate::Error;
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::read;
+
+const PM_TABLE: &str = "/sys/kernel/ryzen_smu_drv/pm_table";
+const PM_TABLE_VERSION: &str = "/sys/kernel/ryzen_smu_drv/pm_table_version";
+
+/// Indexes into the table for the limits and their values
+const PPT_LIMIT: usize = 0;
+const PPT_VALUE: usize = 1;
+const TDC_LIMIT: usize = 2;
+const TDC_VALUE: usize = 3;
+const EDC_LIMIT: usize = 8;
+const EDC_VALUE: usize = 9;
+
+/// The current value and limit of a single smu limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LimitUsage {
+    pub value: f64,
+    pub limit: f64,
+}
+
+impl LimitUsage {
+    /// The fraction of the limit that is used
+    pub fn fraction(&self) -> f64 {
+        self.value / self.limit
+    }
+}
+
+/// The PPT, TDC and EDC limits of the package
+///
+/// The [`Display`](fmt::Display) implementation prints the usage like `PPT: 88/142W TDC: 60/95A EDC: 90/140A`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct SmuLimits {
+    /// Package power tracking in watt
+    pub ppt: LimitUsage,
+    /// Thermal design current, the current the voltage regulators can sustain, in ampere
+    pub tdc: LimitUsage,
+    /// Electrical design current, the peak current the voltage regulators can deliver, in ampere
+    pub edc: LimitUsage,
+    #[cfg(feature = "serde")]
+    #[serde(default)]
+    schema_version: SchemaVersion,
+}
+
+/// Whether the table starts with the limits in the expected order
+fn is_supported(version: u32) -> bool {
+    // Matisse and Vermeer, including their Threadripper variants
+    matches!(version >> 16, 0x24 | 0x38)
+}
+
+impl SmuLimits {
+    /// Read the limits from the smu telemetry
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{smu::SmuLimits, Error};
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let limits = SmuLimits::read()?;
+    ///     println!("{}", limits);
+    ///     println!("{:.0}% of PPT used", limits.ppt.fraction() * 100.0);
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn read() -> Result<Self, Error> {
+        let version = read(PM_TABLE_VERSION).map_err(Error::Smu)?;
+        let version = version
+            .get(..4)
+            .map(|version| u32::from_le_bytes(version.try_into().unwrap()))
+            .ok_or(Error::UnsupportedPmTable(0))?;
+        if !is_supported(version) {
+            return Err(Error::UnsupportedPmTable(version));
+        }
+
+        let table = read(PM_TABLE).map_err(Error::Smu)?;
+        let value = |index: usize| {
+            table
+                .get(index * 4..index * 4 + 4)
+                .map(|value| f32::from_le_bytes(value.try_into().unwrap()) as f64)
+                .ok_or(Error::UnsupportedPmTable(version))
+        };
+        let limit = |limit: usize, value_index: usize| -> Result<LimitUsage, Error> {
+            Ok(LimitUsage {
+                value: value(value_index)?,
+                limit: value(limit)?,
+            })
+        };
+
+        Ok(SmuLimits {
+            ppt: limit(PPT_LIMIT, PPT_VALUE)?,
+            tdc: limit(TDC_LIMIT, TDC_VALUE)?,
+            edc: limit(EDC_LIMIT, EDC_VALUE)?,
+            #[cfg(feature = "serde")]
+            schema_version: SchemaVersion,
+        })
+    }
+}
+
+impl fmt::Display for SmuLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PPT: {:.0}/{:.0}W TDC: {:.0}/{:.0}A EDC: {:.0}/{:.0}A",
+            self.ppt.value,
+            self.ppt.limit,
+            self.tdc.value,
+            self.tdc.limit,
+            self.edc.value,
+            self.edc.limit
+        )
+    }
+}
@@ -0,0 +1,135 @@
+//! Which energy domains the crate reads on a given cpu
+//!
+//! The table only covers the msr interface of AMD cpus, the sysfs sources are detected at runtime.
+
+use crate::descriptor::FamilyDescriptor;
+use std::fmt;
+use std::fs::read_to_string;
+
+/// The microarchitecture of a supported cpu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Generation {
+    /// Zen and Zen+
+    Zen,
+    Zen2,
+    Zen3,
+    Zen4,
+    Zen5,
+    /// Hygon Dhyana, derived from Zen
+    Dhyana,
+}
+
+impl fmt::Display for Generation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Generation::Zen => "Zen/Zen+",
+            Generation::Zen2 => "Zen 2",
+            Generation::Zen3 => "Zen 3",
+            Generation::Zen4 => "Zen 4",
+            Generation::Zen5 => "Zen 5",
+            Generation::Dhyana => "Hygon Dhyana",
+        })
+    }
+}
+
+/// The energy domains and msrs used for a cpu family and model
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SupportedDomains {
+    pub family: u32,
+    pub model: u32,
+    /// The microarchitecture, `None` for unknown parts
+    pub generation: Option<Generation>,
+    /// Whether the per core energy counters are read
+    pub core_energy: bool,
+    /// Whether the package energy counter is read
+    pub package_energy: bool,
+    /// The addresses of the msrs read
+    pub registers: Vec<u64>,
+}
+
+impl SupportedDomains {
+    /// Whether any energy domain is supported
+    pub fn is_supported(&self) -> bool {
+        self.core_energy || self.package_energy
+    }
+}
+
+/// The microarchitecture of a cpu family and model, `None` for unknown parts
+pub fn generation(family: u32, model: u32) -> Option<Generation> {
+    Some(match (family, model) {
+        (0x17, 0x00..=0x2F) => Generation::Zen,
+        (0x17, _) => Generation::Zen2,
+        (0x18, _) => Generation::Dhyana,
+        (0x19, 0x10..=0x1F | 0x60..=0x7F | 0xA0..=0xAF) => Generation::Zen4,
+        (0x19, _) => Generation::Zen3,
+        (0x1A, _) => Generation::Zen5,
+        _ => return None,
+    })
+}
+
+/// Describe the energy domains the crate uses on a cpu with the given family and model
+///
+/// # Example
+///
+/// ```rust
+/// # use ryzen_reader::support::{supported_domains, Generation};
+/// #
+/// let domains = supported_domains(0x19, 0x21);
+/// assert_eq!(domains.generation, Some(Generation::Zen3));
+/// assert!(domains.core_energy && domains.package_energy);
+/// assert!(!supported_domains(0x15, 0x02).is_supported());
+///```
+pub fn supported_domains(family: u32, model: u32) -> SupportedDomains {
+    supported_domains_with(family, model, &FamilyDescriptor::for_cpu(family, model))
+}
+
+/// Describe the energy domains the crate uses on a cpu read with a custom descriptor, see [`FamilyDescriptor::load`]
+pub fn supported_domains_with(
+    family: u32,
+    model: u32,
+    descriptor: &FamilyDescriptor,
+) -> SupportedDomains {
+    let generation = generation(family, model);
+    let supported = generation.is_some();
+    SupportedDomains {
+        family,
+        model,
+        generation,
+        core_energy: supported,
+        package_energy: supported,
+        registers: if supported {
+            vec![
+                descriptor.power_unit,
+                descriptor.core_energy,
+                descriptor.package_energy,
+            ]
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+/// The family and model of the cpu the code is running on, `None` for non AMD or Hygon cpus
+pub fn current_cpu() -> Option<(u32, u32)> {
+    let cpuinfo = read_to_string("/proc/cpuinfo").ok()?;
+    let field = |name: &str| {
+        cpuinfo.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == name).then(|| value.trim())
+        })
+    };
+    if !matches!(field("vendor_id")?, "AuthenticAMD" | "HygonGenuine") {
+        return None;
+    }
+    Some((
+        field("cpu family")?.parse().ok()?,
+        field("model")?.parse().ok()?,
+    ))
+}
+
+/// Describe the energy domains the crate uses on the cpu the code is running on
+pub fn current_domains() -> Option<SupportedDomains> {
+    current_cpu().map(|(family, model)| supported_domains(family, model))
+}
@@ -0,0 +1,164 @@
+//! Power over a sweep of cpu load levels
+//!
+//! The [`LoadGenerator`](crate::load::LoadGenerator) is stepped from idle to full load on a number of cpus while the
+//! power is recorded, giving a load/power curve for every package. Load is placed on the monitored cpus in order,
+//! filling the first package before the next.
+
+use crate::load::{LoadGenerator, LoadPattern};
+use crate::{package_values, CpuInfo, Error};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// The load levels and core counts of a sweep
+#[derive(Debug, Clone)]
+pub struct SweepConfig {
+    steps: u32,
+    core_counts: Vec<usize>,
+    settle: Duration,
+    measure: Duration,
+}
+
+impl Default for SweepConfig {
+    /// Sweep 0 to 100% in steps of 10% on all monitored cpus, settling for 500ms and measuring for a second per step
+    fn default() -> Self {
+        SweepConfig {
+            steps: 11,
+            core_counts: Vec::new(),
+            settle: Duration::from_millis(500),
+            measure: Duration::from_secs(1),
+        }
+    }
+}
+
+impl SweepConfig {
+    /// The number of load levels, evenly spread from 0 to 100%, at least 2
+    pub fn steps(self, steps: u32) -> Self {
+        SweepConfig { steps, ..self }
+    }
+
+    /// The numbers of cpus to run the sweep on, all monitored cpus if empty
+    pub fn core_counts(self, core_counts: &[usize]) -> Self {
+        SweepConfig {
+            core_counts: core_counts.to_vec(),
+            ..self
+        }
+    }
+
+    /// How long to wait after changing the load before measuring
+    pub fn settle(self, settle: Duration) -> Self {
+        SweepConfig { settle, ..self }
+    }
+
+    /// How long to measure the power of every step for
+    pub fn measure(self, measure: Duration) -> Self {
+        SweepConfig { measure, ..self }
+    }
+}
+
+/// The power measured at a single step of a sweep
+#[derive(Debug, Clone)]
+pub struct SweepPoint {
+    /// The number of cpus the load ran on
+    pub cores: usize,
+    /// The load of every loaded cpu, between 0 and 1
+    pub load: f64,
+    /// The power of every package in watt, by package id
+    pub packages: Vec<(u32, f64)>,
+}
+
+/// The results of [`CpuInfo::sweep`]
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    /// All steps by core count and then by load
+    pub points: Vec<SweepPoint>,
+}
+
+impl Sweep {
+    /// The ids of all packages in the sweep
+    pub fn packages(&self) -> Vec<u32> {
+        let mut packages: Vec<u32> = self
+            .points
+            .iter()
+            .flat_map(|point| point.packages.iter().map(|(package, _)| *package))
+            .collect();
+        packages.sort_unstable();
+        packages.dedup();
+        packages
+    }
+
+    /// The load/power curve of a package as (cores, load, watt)
+    pub fn curve(&self, package: u32) -> impl Iterator<Item = (usize, f64, f64)> + '_ {
+        self.points.iter().filter_map(move |point| {
+            let (_, power) = point.packages.iter().find(|(id, _)| *id == package)?;
+            Some((point.cores, point.load, *power))
+        })
+    }
+}
+
+impl CpuInfo {
+    /// Step the load from idle to full on every configured number of cpus and measure the power of every step
+    ///
+    /// This takes `(settle + measure) * steps` for every core count.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ryzen_reader::{CpuInfo, Error};
+    /// # use ryzen_reader::sweep::SweepConfig;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    ///     let cpu = CpuInfo::new()?;
+    ///     let sweep = cpu.sweep(&SweepConfig::default().core_counts(&[1, 4]))?;
+    ///     for (cores, load, power) in sweep.curve(0) {
+    ///         println!("{} cores at {:.0}%: {:.2}W", cores, load * 100.0, power);
+    ///     }
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn sweep(&self, config: &SweepConfig) -> Result<Sweep, Error> {
+        let cpus: Vec<u32> = self.cores.iter().map(|core| core.cpu).collect();
+        let core_counts = if config.core_counts.is_empty() {
+            vec![cpus.len()]
+        } else {
+            config.core_counts.clone()
+        };
+        if config.steps < 2 {
+            return Err(Error::InvalidSweep("at least two load steps are needed"));
+        }
+        if core_counts
+            .iter()
+            .any(|&count| count == 0 || count > cpus.len())
+        {
+            return Err(Error::InvalidSweep(
+                "core counts have to be between one and the number of monitored cpus",
+            ));
+        }
+        if config.measure.is_zero() {
+            return Err(Error::EmptyInterval);
+        }
+        if config.measure > self.max_interval() {
+            return Err(Error::IntervalTooLong {
+                interval: config.measure,
+                max: self.max_interval(),
+            });
+        }
+
+        let mut points = Vec::with_capacity(core_counts.len() * config.steps as usize);
+        for cores in core_counts {
+            for step in 0..config.steps {
+                let load = f64::from(step) / f64::from(config.steps - 1);
+                let generator = LoadGenerator::start(&cpus[..cores], LoadPattern::duty(load))?;
+                sleep(config.settle);
+                let power = self.read_with_interval(config.measure)?;
+                generator.stop();
+                points.push(SweepPoint {
+                    cores,
+                    load,
+                    packages: package_values(&power.cores, power.package_aggregation).collect(),
+                });
+            }
+        }
+
+        Ok(Sweep { points })
+    }
+}
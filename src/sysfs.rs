@@ -0,0 +1,38 @@
+use std::fs::read_to_string;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Read a sysfs attribute with the trailing newline removed
+pub fn read_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut value = read_to_string(path)?;
+    let len = value.trim_end().len();
+    value.truncate(len);
+    Ok(value)
+}
+
+/// Read and parse a sysfs attribute
+pub fn read_value<T: FromStr, P: AsRef<Path>>(path: P) -> io::Result<T> {
+    read_string(path)?
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid sysfs value"))
+}
+
+/// Parse a kernel cpu list like `0-3,8,10-11` into the individual cpu ids
+pub fn parse_cpu_list(list: &str) -> io::Result<Vec<u32>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid cpu list");
+    let mut cpus = Vec::new();
+
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => {
+                let start: u32 = start.parse().map_err(|_| invalid())?;
+                let end: u32 = end.parse().map_err(|_| invalid())?;
+                cpus.extend(start..=end);
+            }
+            None => cpus.push(range.parse().map_err(|_| invalid())?),
+        }
+    }
+
+    Ok(cpus)
+}
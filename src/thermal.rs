@@ -0,0 +1,150 @@
+//! Cpu temperatures from the k10temp hwmon driver
+//!
+//! k10temp registers a device for every package, exposing the control temperature (Tctl),
+//! on some older cpus the actual die temperature (Tdie) and on Zen 2 and later the temperature of every ccd (Tccd1..n).
+//! Tctl can include an offset above the real temperature and is the value the fan control should use.
+
+use crate::hwmon::{hwmon_devices, labelled_inputs};
+#[cfg(feature = "serde")]
+use crate::schema::SchemaVersion;
+use crate::sysfs::read_value;
+use crate::{CpuInfo, CpuPower, Error};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// The temperature inputs of a single package
+#[derive(Debug, Clone)]
+struct PackageSensors {
+    tctl: Option<PathBuf>,
+    tdie: Option<PathBuf>,
+    ccds: Vec<PathBuf>,
+}
+
+/// The temperatures of a single package in degrees celsius
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct PackageTemperatures {
+    pub package: u32,
+    /// The control temperature, `None` if it can't be read
+    pub tctl: Option<f64>,
+    /// The die temperature, only reported by cpus where Tctl includes an offset
+    pub tdie: Option<f64>,
+    /// The temperature of every ccd, in ccd order, empty if not reported by the cpu
+    pub ccds: Vec<f64>,
+    #[cfg(feature = "serde")]
+    #[serde(default)]
+    schema_version: SchemaVersion,
+}
+
+/// Power and temperatures taken together by [`CpuInfo::read_with_temperatures`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ThermalSample {
+    pub power: CpuPower,
+    /// The temperatures of every package, read at the end of the power reading
+    pub temperatures: Vec<PackageTemperatures>,
+}
+
+/// The k10temp temperature sensors of all packages
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::thermal::ThermalSensors;
+/// #
+/// if let Some(sensors) = ThermalSensors::find() {
+///     for package in sensors.read() {
+///         println!("#{}: {:?}C", package.package, package.tctl);
+///     }
+/// }
+///```
+#[derive(Debug, Clone)]
+pub struct ThermalSensors {
+    packages: Vec<PackageSensors>,
+}
+
+/// Read a temperature input in degrees celsius
+fn read_temperature(path: &Path) -> Option<f64> {
+    read_value::<i64, _>(path)
+        .ok()
+        .map(|millidegrees| millidegrees as f64 / 1000.0)
+}
+
+impl ThermalSensors {
+    /// Find the k10temp devices, `None` if the k10temp driver isn't loaded
+    pub fn find() -> Option<Self> {
+        let packages: Vec<PackageSensors> = hwmon_devices("k10temp")
+            .iter()
+            .map(|device| {
+                let mut inputs = labelled_inputs(device, "temp");
+                let mut ccds: Vec<(u32, PathBuf)> = inputs
+                    .iter()
+                    .filter_map(|(label, path)| {
+                        Some((label.strip_prefix("Tccd")?.parse().ok()?, path.clone()))
+                    })
+                    .collect();
+                ccds.sort();
+                PackageSensors {
+                    // Tctl is always the first input, older kernels don't label it
+                    tctl: inputs
+                        .remove("Tctl")
+                        .or_else(|| Some(device.join("temp1_input")).filter(|path| path.exists())),
+                    tdie: inputs.remove("Tdie"),
+                    ccds: ccds.into_iter().map(|(_, path)| path).collect(),
+                }
+            })
+            .collect();
+        (!packages.is_empty()).then_some(ThermalSensors { packages })
+    }
+
+    /// Read the temperatures of every package, ordered by package
+    pub fn read(&self) -> Vec<PackageTemperatures> {
+        self.packages
+            .iter()
+            .zip(0..)
+            .map(|(sensors, package)| PackageTemperatures {
+                package,
+                tctl: sensors.tctl.as_deref().and_then(read_temperature),
+                tdie: sensors.tdie.as_deref().and_then(read_temperature),
+                ccds: sensors
+                    .ccds
+                    .iter()
+                    .filter_map(|path| read_temperature(path))
+                    .collect(),
+                #[cfg(feature = "serde")]
+                schema_version: SchemaVersion,
+            })
+            .collect()
+    }
+
+    /// The control temperature of the first package, the usual input for fan control
+    pub fn tctl(&self) -> Option<f64> {
+        read_temperature(self.packages.first()?.tctl.as_deref()?)
+    }
+}
+
+impl CpuInfo {
+    /// Read the cpu power levels averaged over `interval` and the temperatures at the end of the interval
+    pub fn read_with_temperatures(
+        &self,
+        sensors: &ThermalSensors,
+        interval: Duration,
+    ) -> Result<ThermalSample, Error> {
+        let max = self.max_interval();
+        if interval > max {
+            return Err(Error::IntervalTooLong { interval, max });
+        }
+
+        let start = self.snapshot()?;
+        sleep(interval);
+        let end = self.snapshot()?;
+        let temperatures = sensors.read();
+
+        Ok(ThermalSample {
+            power: CpuPower::between(&start, &end)?,
+            temperatures,
+        })
+    }
+}
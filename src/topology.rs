@@ -0,0 +1,278 @@
+use crate::sysfs::{parse_cpu_list, read_string, read_value};
+use crate::{Core, Error};
+use std::fs::{read_to_string, rename, write, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// Topology and capability report for the cpus being monitored
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Topology {
+    pub cores: Vec<CoreTopology>,
+}
+
+impl Topology {
+    /// The ids of all packages in the system
+    pub fn packages(&self) -> impl Iterator<Item = u32> + '_ {
+        let mut last_package = u32::MAX;
+
+        self.cores.iter().filter_map(move |core| {
+            if core.package != last_package {
+                last_package = core.package;
+                Some(core.package)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Topology information for a single logical cpu
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct CoreTopology {
+    /// The logical cpu id
+    pub cpu: u32,
+    /// The physical package the cpu belongs to
+    pub package: u32,
+    /// Scaling governor and frequencies, `None` if cpufreq isn't available for the cpu
+    pub frequency: Option<FrequencyInfo>,
+    /// Performance levels and energy preference, `None` if the amd-pstate driver isn't used for the cpu
+    pub amd_pstate: Option<AmdPstateInfo>,
+    /// Whether the core energy domain can be read on this cpu
+    pub core_energy: bool,
+    /// Whether the package energy domain can be read on this cpu
+    pub package_energy: bool,
+}
+
+/// cpufreq scaling state of a single cpu
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrequencyInfo {
+    /// The active scaling governor, e.g. `schedutil` or `performance`
+    pub governor: String,
+    /// Current scaling frequency in kHz
+    pub current: u32,
+    /// Minimum scaling frequency in kHz
+    pub min: u32,
+    /// Maximum scaling frequency in kHz
+    pub max: u32,
+}
+
+impl FrequencyInfo {
+    pub(crate) fn read(cpu: u32) -> Option<Self> {
+        let base = format!("/sys/devices/system/cpu/cpu{}/cpufreq", cpu);
+        Some(FrequencyInfo {
+            governor: read_string(format!("{}/scaling_governor", base)).ok()?,
+            current: read_value(format!("{}/scaling_cur_freq", base)).ok()?,
+            min: read_value(format!("{}/scaling_min_freq", base)).ok()?,
+            max: read_value(format!("{}/scaling_max_freq", base)).ok()?,
+        })
+    }
+}
+
+/// amd-pstate state of a single cpu
+///
+/// The performance levels are abstract CPPC values, the highest level is only reachable while boosting
+/// and the lowest nonlinear level is the most power efficient one.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AmdPstateInfo {
+    /// The active driver, `amd-pstate` or `amd-pstate-epp`
+    pub driver: String,
+    pub highest_perf: Option<u32>,
+    pub nominal_perf: Option<u32>,
+    pub lowest_nonlinear_perf: Option<u32>,
+    /// The frequency of the lowest nonlinear performance level in kHz
+    pub lowest_nonlinear_freq: Option<u32>,
+    /// The energy performance preference, e.g. `balance_performance`, only set by `amd-pstate-epp`
+    pub epp: Option<String>,
+}
+
+impl AmdPstateInfo {
+    pub(crate) fn read(cpu: u32) -> Option<Self> {
+        let base = format!("/sys/devices/system/cpu/cpu{}", cpu);
+        let driver = read_string(format!("{}/cpufreq/scaling_driver", base)).ok()?;
+        if !driver.starts_with("amd-pstate") {
+            return None;
+        }
+        let cppc = |name: &str| read_value(format!("{}/acpi_cppc/{}", base, name)).ok();
+        Some(AmdPstateInfo {
+            highest_perf: read_value(format!("{}/cpufreq/amd_pstate_highest_perf", base))
+                .ok()
+                .or_else(|| cppc("highest_perf")),
+            nominal_perf: cppc("nominal_perf"),
+            lowest_nonlinear_perf: cppc("lowest_nonlinear_perf"),
+            lowest_nonlinear_freq: read_value(format!(
+                "{}/cpufreq/amd_pstate_lowest_nonlinear_freq",
+                base
+            ))
+            .ok(),
+            epp: read_string(format!("{}/cpufreq/energy_performance_preference", base)).ok(),
+            driver,
+        })
+    }
+}
+
+/// The lowest logical cpu id of the physical core `cpu` belongs to, `cpu` itself if the siblings can't be read
+pub(crate) fn physical_core(cpu: u32) -> u32 {
+    read_string(format!(
+        "/sys/devices/system/cpu/cpu{}/topology/thread_siblings_list",
+        cpu
+    ))
+    .ok()
+    .and_then(|siblings| parse_cpu_list(&siblings).ok())
+    .and_then(|siblings| siblings.into_iter().min())
+    .unwrap_or(cpu)
+}
+
+/// The core complex die `cpu` belongs to
+///
+/// Reported through the id of the L3 cache, which is shared by all cores of a ccd on Zen 3 and later.
+/// On Zen 2 and earlier every ccd contains two L3 caches, so this identifies the core complex instead.
+pub(crate) fn ccd(cpu: u32) -> Option<u32> {
+    let base = format!("/sys/devices/system/cpu/cpu{}/cache/index3", cpu);
+    if read_value::<u32, _>(format!("{}/level", base)).ok()? != 3 {
+        return None;
+    }
+    read_value(format!("{}/id", base)).ok()
+}
+
+/// Whether `cpu` is present and online
+pub(crate) fn is_online(cpu: u32) -> bool {
+    let base = format!("/sys/devices/system/cpu/cpu{}", cpu);
+    match read_value::<u8, _>(format!("{}/online", base)) {
+        Ok(online) => online == 1,
+        // cpus that can't be taken offline don't have an online attribute
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Path::new(&base).exists(),
+        Err(_) => true,
+    }
+}
+
+/// The ids of all online cpus
+pub(crate) fn online_cpus() -> Result<Vec<u32>, Error> {
+    let online = read_string("/sys/devices/system/cpu/online")?;
+    Ok(parse_cpu_list(&online)?)
+}
+
+/// Find the package of every online cpu as `(cpu, package)` pairs, optionally limited to a set of cpus
+pub(crate) fn discover(only: Option<&[u32]>) -> Result<Vec<(u32, u32)>, Error> {
+    let online = online_cpus()?;
+    let mut layout = Vec::with_capacity(online.len());
+
+    for cpu in online
+        .into_iter()
+        .filter(|cpu| only.is_none_or(|only| only.contains(cpu)))
+    {
+        match Core::read_package(cpu) {
+            Ok(package) => layout.push((cpu, package)),
+            // the cpu went offline since the mask was read
+            Err(Error::CoreNotFound) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(layout)
+}
+
+/// The position of a cpu in the topology, as stored in the topology cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CpuPlacement {
+    pub cpu: u32,
+    pub package: u32,
+    pub physical: u32,
+    pub ccd: Option<u32>,
+}
+
+impl CpuPlacement {
+    pub fn read(cpu: u32, package: u32) -> Self {
+        CpuPlacement {
+            cpu,
+            package,
+            physical: physical_core(cpu),
+            ccd: ccd(cpu),
+        }
+    }
+}
+
+/// Version of the cache format, caches written in another format are rebuilt
+const CACHE_VERSION: &str = "ryzen-reader topology 2";
+
+/// Key used to validate the topology cache, consisting of the format version, cpu model and online cpu mask
+fn cache_key() -> io::Result<String> {
+    let cpuinfo = BufReader::new(File::open("/proc/cpuinfo")?);
+    let mut model = None;
+    for line in cpuinfo.lines() {
+        let line = line?;
+        if line.starts_with("model name") {
+            model = Some(line);
+            break;
+        }
+    }
+    let model = model.ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+    let online = read_string("/sys/devices/system/cpu/online")?;
+    Ok(format!("{}\n{}\n{}", CACHE_VERSION, model, online))
+}
+
+/// Load the cached placement of every cpu, `None` if the cache is missing, outdated or incomplete
+pub(crate) fn load_cached(path: &Path) -> Option<Vec<CpuPlacement>> {
+    let key = cache_key().ok()?;
+    let cached = read_to_string(path).ok()?;
+    let layout = cached.strip_prefix(&key)?.strip_prefix('\n')?;
+
+    layout
+        .lines()
+        .map(|line| {
+            let mut parts = line.split(' ');
+            let cpu = parts.next()?.parse().ok()?;
+            let package = parts.next()?.parse().ok()?;
+            let physical = parts.next()?.parse().ok()?;
+            let ccd = match parts.next()? {
+                "-" => None,
+                ccd => Some(ccd.parse().ok()?),
+            };
+            Some(CpuPlacement {
+                cpu,
+                package,
+                physical,
+                ccd,
+            })
+        })
+        .collect::<Option<Vec<_>>>()
+        .filter(|layout| !layout.is_empty())
+}
+
+/// Write the topology cache
+///
+/// The cache is written to a temporary file that is renamed over the cache,
+/// so concurrent readers never see a partially written cache.
+pub(crate) fn store_cached(path: &Path, layout: &[CpuPlacement]) {
+    let key = match cache_key() {
+        Ok(key) => key,
+        Err(_) => return,
+    };
+    let mut cache = key;
+    cache.push('\n');
+    for placement in layout {
+        let ccd = placement
+            .ccd
+            .map_or_else(|| "-".to_string(), |ccd| ccd.to_string());
+        cache.push_str(&format!(
+            "{} {} {} {}\n",
+            placement.cpu, placement.package, placement.physical, ccd
+        ));
+    }
+
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return,
+    };
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+    if write(&temp, cache)
+        .and_then(|_| rename(&temp, path))
+        .is_err()
+    {
+        let _ = std::fs::remove_file(&temp);
+    }
+}
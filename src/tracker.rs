@@ -0,0 +1,167 @@
+use crate::shared::SharedSampler;
+use crate::{CpuPower, EnergySnapshot, Error};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Ticket ids are unique over all trackers, so a ticket of another tracker is never mistaken for one of ours
+static NEXT_TICKET: AtomicU64 = AtomicU64::new(0);
+
+/// Aggregated energy usage of a request class
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassEnergy {
+    /// Number of finished requests
+    pub requests: u64,
+    /// Total energy attributed to the finished requests in joule
+    pub energy: f64,
+}
+
+impl ClassEnergy {
+    /// Average energy per request in joule
+    pub fn average(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.energy / self.requests as f64
+        }
+    }
+}
+
+/// Handle for a request being tracked, pass it to [`EnergyBudgetTracker::end`] when the request is done
+#[derive(Debug)]
+#[must_use]
+pub struct RequestTicket {
+    id: u64,
+}
+
+#[derive(Debug)]
+struct ActiveRequest {
+    class: String,
+    energy: f64,
+}
+
+#[derive(Debug)]
+struct TrackerState {
+    last: EnergySnapshot,
+    active: HashMap<u64, ActiveRequest>,
+    classes: HashMap<String, ClassEnergy>,
+}
+
+/// Estimate the energy used per request
+///
+/// The package energy used while requests are in flight is split evenly between all requests active at the time,
+/// so concurrent requests don't all get charged for the full energy used.
+/// Energy used while no requests are active is not attributed to any request.
+///
+/// The counters are read through a [`SharedSampler`], requests starting or ending within its freshness window
+/// share a single snapshot instead of every request reading every msr.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use ryzen_reader::Error;
+/// # use ryzen_reader::tracker::EnergyBudgetTracker;
+/// #
+/// # fn main() -> Result<(), Error> {
+///     let tracker = EnergyBudgetTracker::global()?;
+///
+///     let ticket = tracker.begin("GET /index")?;
+///     // handle the request
+///     let joules = tracker.end(ticket)?;
+///
+///     println!("{:.3}J", tracker.class("GET /index").average());
+/// #     Ok(())
+/// # }
+///```
+pub struct EnergyBudgetTracker {
+    sampler: Arc<SharedSampler>,
+    state: Mutex<TrackerState>,
+}
+
+impl EnergyBudgetTracker {
+    pub fn new(sampler: Arc<SharedSampler>) -> Result<Self, Error> {
+        let last = sampler.snapshot()?;
+        Ok(EnergyBudgetTracker {
+            sampler,
+            state: Mutex::new(TrackerState {
+                last,
+                active: HashMap::new(),
+                classes: HashMap::new(),
+            }),
+        })
+    }
+
+    /// A tracker using the process wide [`SharedSampler::global`]
+    pub fn global() -> Result<Self, Error> {
+        Self::new(SharedSampler::global()?)
+    }
+
+    /// Split the energy used since the last update over the active requests
+    fn advance(&self, state: &mut TrackerState) -> Result<(), Error> {
+        let snapshot = self.sampler.snapshot()?;
+        if snapshot.time() <= state.last.time() {
+            // the shared snapshot is still fresh, no new energy to split
+            return Ok(());
+        }
+        let last = std::mem::replace(&mut state.last, snapshot);
+        if state.active.is_empty() {
+            return Ok(());
+        }
+
+        let energy: f64 = CpuPower::between(&last, &state.last)?
+            .package_energy()
+            .sum();
+        let share = energy / state.active.len() as f64;
+        for request in state.active.values_mut() {
+            request.energy += share;
+        }
+        Ok(())
+    }
+
+    /// Start tracking a request of the given class
+    pub fn begin(&self, class: &str) -> Result<RequestTicket, Error> {
+        let mut state = self.state.lock().unwrap();
+        self.advance(&mut state)?;
+
+        let id = NEXT_TICKET.fetch_add(1, Ordering::Relaxed);
+        state.active.insert(
+            id,
+            ActiveRequest {
+                class: class.to_string(),
+                energy: 0.0,
+            },
+        );
+        Ok(RequestTicket { id })
+    }
+
+    /// Stop tracking a request, returns the energy attributed to it in joule
+    ///
+    /// Tickets created by another tracker are rejected with [`Error::UnknownTicket`].
+    pub fn end(&self, ticket: RequestTicket) -> Result<f64, Error> {
+        let mut state = self.state.lock().unwrap();
+        if !state.active.contains_key(&ticket.id) {
+            return Err(Error::UnknownTicket);
+        }
+        self.advance(&mut state)?;
+
+        let request = state
+            .active
+            .remove(&ticket.id)
+            .ok_or(Error::UnknownTicket)?;
+        let class = state.classes.entry(request.class).or_default();
+        class.requests += 1;
+        class.energy += request.energy;
+        Ok(request.energy)
+    }
+
+    /// Get the aggregated energy usage of a request class
+    pub fn class(&self, class: &str) -> ClassEnergy {
+        let state = self.state.lock().unwrap();
+        state.classes.get(class).copied().unwrap_or_default()
+    }
+
+    /// Get the aggregated energy usage of all request classes
+    pub fn classes(&self) -> HashMap<String, ClassEnergy> {
+        self.state.lock().unwrap().classes.clone()
+    }
+}
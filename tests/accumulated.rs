@@ -0,0 +1,67 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::clock::ManualClock;
+use ryzen_reader::CpuInfo;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn mock_cpu() -> (Arc<ManualClock>, Arc<MockBackend>, CpuInfo) {
+    let clock = Arc::new(ManualClock::new());
+    let backend = Arc::new(MockBackend::with_clock(1, 2, clock.clone()));
+    backend.set_power(0, 4.0, 20.0);
+    backend.set_power(1, 6.0, 20.0);
+    let cpu = CpuInfo::builder()
+        .backend(backend.clone())
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    (clock, backend, cpu)
+}
+
+#[test]
+fn sparse_sampling_gives_the_long_term_average() {
+    let (clock, backend, cpu) = mock_cpu();
+    // 10 seconds at the initial power, then 30 seconds at a higher power, with only a read in between
+    clock.advance(Duration::from_secs(10));
+    cpu.snapshot().unwrap();
+    backend.set_power(0, 8.0, 20.0);
+    clock.advance(Duration::from_secs(30));
+
+    let energy = cpu.energy_since_init().unwrap();
+    assert_eq!(energy.elapsed(), Duration::from_secs(40));
+    let averages: Vec<_> = energy.average_cores().collect();
+    assert!((averages[0] - 7.0).abs() < 0.01, "{:?}", averages);
+    assert!((averages[1] - 6.0).abs() < 0.01, "{:?}", averages);
+}
+
+#[test]
+fn estimates_use_the_rapl_time_unit() {
+    let (clock, _backend, cpu) = mock_cpu();
+    clock.advance(Duration::from_secs(2));
+    let energy = cpu.energy_since_init().unwrap();
+
+    // the mock reports a time unit of 1/1024 s and an energy unit of 1/65536 J
+    assert_eq!(energy.time_unit(), 1.0 / 1024.0);
+    let estimates: Vec<_> = energy.average_core_estimates().collect();
+    assert_eq!(estimates.len(), 2);
+    let expected = 2.0 * (1.0 / 65536.0 + 4.0 / 1024.0) / 2.0;
+    assert!((estimates[0].power - 4.0).abs() < 0.01, "{:?}", estimates);
+    assert!(
+        (estimates[0].uncertainty - expected).abs() < 1e-4,
+        "{:?}",
+        estimates
+    );
+    assert!(estimates[1].uncertainty > estimates[0].uncertainty);
+
+    let packages: Vec<_> = energy.average_package_estimates().collect();
+    assert_eq!(packages.len(), 1);
+    assert!((packages[0].power - 20.0).abs() < 0.01, "{:?}", packages);
+}
+
+#[test]
+fn no_estimate_within_a_single_time_unit() {
+    let (clock, _backend, cpu) = mock_cpu();
+    clock.advance(Duration::from_micros(500));
+    let energy = cpu.energy_since_init().unwrap();
+    assert_eq!(energy.average_core_estimates().count(), 0);
+    assert_eq!(energy.average_package_estimates().count(), 0);
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "prometheus")]
+
+//! A single test, socket activation always passes the listener as file descriptor 3
+
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::prometheus::{activated_listener, ActivatedListener, Exporter};
+use ryzen_reader::CpuInfo;
+use std::env::{set_var, var};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::thread::spawn;
+
+const LISTEN_FD: i32 = 3;
+
+/// Pass `listener` as file descriptor 3 like systemd does and take it back through the activation
+///
+/// Whatever was open as descriptor 3 before is restored afterwards.
+fn activate<L: AsRawFd>(listener: &L, pid: u32) -> Option<ActivatedListener> {
+    let saved = unsafe { libc::fcntl(LISTEN_FD, libc::F_DUPFD_CLOEXEC, 10) };
+    assert_eq!(
+        unsafe { libc::dup2(listener.as_raw_fd(), LISTEN_FD) },
+        LISTEN_FD
+    );
+    set_var("LISTEN_PID", pid.to_string());
+    set_var("LISTEN_FDS", "1");
+
+    let activated = activated_listener();
+    assert!(var("LISTEN_PID").is_err() && var("LISTEN_FDS").is_err());
+
+    // move the activated listener off descriptor 3 before restoring it
+    let activated = activated.map(|activated| match activated {
+        ActivatedListener::Tcp(listener) => {
+            ActivatedListener::Tcp(unsafe { TcpListener::from_raw_fd(moved(listener)) })
+        }
+        ActivatedListener::Unix(listener) => {
+            ActivatedListener::Unix(unsafe { UnixListener::from_raw_fd(moved(listener)) })
+        }
+    });
+    if saved >= 0 {
+        unsafe {
+            libc::dup2(saved, LISTEN_FD);
+            libc::close(saved);
+        }
+    } else {
+        unsafe { libc::close(LISTEN_FD) };
+    }
+    activated
+}
+
+/// Duplicate the activated descriptor 3, which is closed or replaced when restoring it
+fn moved<F: IntoRawFd>(fd: F) -> i32 {
+    let moved = unsafe { libc::fcntl(fd.into_raw_fd(), libc::F_DUPFD_CLOEXEC, 10) };
+    assert!(moved >= 0);
+    moved
+}
+
+fn exporter() -> Exporter {
+    let backend = MockBackend::new(1, 2);
+    backend.set_power(0, 5.0, 20.0);
+    Exporter::new(CpuInfo::builder().backend(backend).build().unwrap())
+}
+
+fn get<S: Read + Write>(mut stream: S) -> String {
+    write!(stream, "GET /healthz HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn socket_activation() {
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = tcp.local_addr().unwrap();
+
+    // activation for another process is ignored
+    assert!(activate(&tcp, std::process::id() + 1).is_none());
+    // only listening sockets are accepted
+    let connected = TcpStream::connect(address).unwrap();
+    assert!(activate(&connected, std::process::id()).is_none());
+    drop(connected);
+
+    let listener = match activate(&tcp, std::process::id()) {
+        Some(ActivatedListener::Tcp(listener)) => listener,
+        activated => panic!("unexpected activation: {:?}", activated),
+    };
+    drop(tcp);
+    spawn(move || exporter().serve_listener(listener));
+    let response = get(TcpStream::connect(address).unwrap());
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+
+    let path = std::env::temp_dir().join(format!(
+        "ryzen-reader-activation-{}.sock",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let unix = UnixListener::bind(&path).unwrap();
+    let listener = match activate(&unix, std::process::id()) {
+        Some(ActivatedListener::Unix(listener)) => listener,
+        activated => panic!("unexpected activation: {:?}", activated),
+    };
+    drop(unix);
+    spawn(move || exporter().serve_unix_listener(listener));
+    let response = get(UnixStream::connect(&path).unwrap());
+    assert!(response.contains("\r\n\r\nstatus ok\n"), "{}", response);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,117 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::benchmark::{load_source, save_source};
+use ryzen_reader::{CpuInfo, Error, Source};
+use std::fs::{remove_file, write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn mock(package: f64) -> Source {
+    let backend = MockBackend::new(1, 2);
+    backend.set_power(0, 5.0, package);
+    backend.set_power(1, 5.0, package);
+    Source::Backend(Arc::new(backend))
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "ryzen-reader-benchmark-{}-{}",
+        name,
+        std::process::id()
+    ))
+}
+
+#[test]
+fn disagreeing_source_is_not_recommended() {
+    let benchmark = CpuInfo::benchmark_sources(
+        &[mock(20.0), mock(30.0), mock(20.0)],
+        Duration::from_millis(100),
+    )
+    .unwrap();
+    assert_eq!(benchmark.sources.len(), 3);
+    for result in &benchmark.sources {
+        assert_eq!(result.name, "backend");
+        assert!(result.latency > Duration::ZERO);
+    }
+    assert!(benchmark.sources[0].deviation < 0.05);
+    assert!(benchmark.sources[1].deviation > 0.4);
+
+    let recommended = benchmark.recommended().unwrap();
+    assert!(
+        (recommended.package_power - 20.0).abs() < 1.0,
+        "{:?}",
+        recommended
+    );
+}
+
+#[test]
+fn no_recommendation_without_agreement() {
+    let benchmark =
+        CpuInfo::benchmark_sources(&[mock(20.0), mock(30.0)], Duration::from_millis(50)).unwrap();
+    assert!(benchmark.recommended().is_none());
+    assert!(matches!(
+        benchmark.save(temp_file("none")),
+        Err(Error::SourceUnavailable)
+    ));
+}
+
+#[test]
+fn unusable_sources_are_left_out() {
+    let empty = Source::Backend(Arc::new(MockBackend::new(1, 0)));
+    let benchmark =
+        CpuInfo::benchmark_sources(&[empty.clone(), mock(20.0)], Duration::from_millis(50))
+            .unwrap();
+    assert_eq!(benchmark.sources.len(), 1);
+    assert!(benchmark.recommended().is_some());
+
+    assert!(matches!(
+        CpuInfo::benchmark_sources(&[empty], Duration::from_millis(50)),
+        Err(Error::SourceUnavailable)
+    ));
+    assert!(matches!(
+        CpuInfo::benchmark_sources(&[mock(20.0)], Duration::ZERO),
+        Err(Error::EmptyInterval)
+    ));
+}
+
+#[test]
+fn source_choice_is_persisted() {
+    let path = temp_file("persist");
+    assert!(load_source(&path).unwrap().is_none());
+
+    save_source(&Source::Msr, &path).unwrap();
+    assert!(matches!(load_source(&path).unwrap(), Some(Source::Msr)));
+    save_source(&Source::Sysfs, &path).unwrap();
+    assert!(matches!(load_source(&path).unwrap(), Some(Source::Sysfs)));
+
+    // custom backends can't be restored by a later run
+    assert!(save_source(&mock(20.0), &path).is_err());
+    assert!(matches!(load_source(&path).unwrap(), Some(Source::Sysfs)));
+
+    write(&path, "something else\nmsr\n").unwrap();
+    assert!(load_source(&path).unwrap().is_none());
+    remove_file(path).unwrap();
+}
+
+#[test]
+fn saved_source_is_used_when_building() {
+    let path = temp_file("build");
+    save_source(&Source::Sysfs, &path).unwrap();
+    let result = CpuInfo::builder()
+        .backend(MockBackend::new(1, 1))
+        .saved_source(&path)
+        .build();
+    match result {
+        Ok(cpu) => assert_ne!(cpu.source(), "backend"),
+        Err(e) => assert!(matches!(e, Error::SourceUnavailable), "{:?}", e),
+    }
+    remove_file(&path).unwrap();
+
+    // without a saved choice the configured source is kept
+    let cpu = CpuInfo::builder()
+        .backend(MockBackend::new(1, 1))
+        .saved_source(&path)
+        .build()
+        .unwrap();
+    assert_eq!(cpu.source(), "backend");
+}
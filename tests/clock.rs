@@ -0,0 +1,95 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::clock::{Clock, ManualClock};
+use ryzen_reader::{CpuInfo, CpuPower, Error};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn mock_cpu() -> (Arc<ManualClock>, CpuInfo) {
+    let clock = Arc::new(ManualClock::new());
+    let backend = MockBackend::with_clock(1, 2, clock.clone());
+    backend.set_power(0, 5.0, 20.0);
+    backend.set_power(1, 5.0, 20.0);
+    let cpu = CpuInfo::builder()
+        .backend(backend)
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    (clock, cpu)
+}
+
+#[test]
+fn rewind_stops_at_creation() {
+    let clock = ManualClock::new();
+    let created = clock.now();
+
+    clock.rewind(Duration::from_secs(3600));
+    assert_eq!(clock.now(), created);
+
+    clock.advance(Duration::from_secs(10));
+    clock.rewind(Duration::from_secs(4));
+    assert_eq!(clock.now(), created + Duration::from_secs(6));
+    clock.rewind(Duration::from_secs(60));
+    assert_eq!(clock.now(), created);
+}
+
+#[test]
+fn rewound_clock_gives_empty_interval() {
+    let (clock, cpu) = mock_cpu();
+    clock.advance(Duration::from_secs(5));
+    let start = cpu.snapshot().unwrap();
+    clock.rewind(Duration::from_secs(2));
+    let end = cpu.snapshot().unwrap();
+
+    assert!(matches!(
+        CpuPower::between(&start, &end),
+        Err(Error::EmptyInterval)
+    ));
+}
+
+#[test]
+fn stalled_clock_gives_empty_interval() {
+    let (_clock, cpu) = mock_cpu();
+    // the manual clock doesn't move during the sleep
+    assert!(matches!(
+        cpu.read_with_interval(Duration::from_millis(10)),
+        Err(Error::EmptyInterval)
+    ));
+}
+
+#[test]
+fn large_forward_jump_is_rejected() {
+    let (clock, cpu) = mock_cpu();
+    let max = cpu.max_interval();
+
+    let start = cpu.snapshot().unwrap();
+    clock.advance(max + Duration::from_secs(1));
+    let end = cpu.snapshot().unwrap();
+    match CpuPower::between(&start, &end) {
+        Err(Error::IntervalTooLong {
+            interval,
+            max: limit,
+        }) => {
+            assert_eq!(interval, max + Duration::from_secs(1));
+            assert_eq!(limit, max);
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // rejected up front instead of after sleeping for the interval
+    assert!(matches!(
+        cpu.read_with_interval(max * 2),
+        Err(Error::IntervalTooLong { .. })
+    ));
+}
+
+#[test]
+fn jump_within_max_interval_is_measured() {
+    let (clock, cpu) = mock_cpu();
+    let interval = cpu.max_interval() / 2;
+
+    let start = cpu.snapshot().unwrap();
+    clock.advance(interval);
+    let power = CpuPower::between(&start, &cpu.snapshot().unwrap()).unwrap();
+    let packages: Vec<_> = power.packages().map(f64::round).collect();
+    assert_eq!(packages, [20.0]);
+}
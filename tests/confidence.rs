@@ -0,0 +1,84 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::{CpuInfo, Error};
+use std::sync::Arc;
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+#[test]
+fn long_interval_is_rejected_up_front() {
+    let cpu = CpuInfo::builder()
+        .backend(MockBackend::new(1, 1))
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    assert!(matches!(
+        cpu.read_with_confidence(cpu.max_interval() * 2, 4),
+        Err(Error::IntervalTooLong { .. })
+    ));
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn estimates_are_matched_by_cpu() {
+    let backend = Arc::new(MockBackend::new(1, 3));
+    backend.set_power(0, 10.0, 30.0);
+    backend.set_power(1, 1.0, 30.0);
+    backend.set_power(2, 2.0, 30.0);
+    let cpu = CpuInfo::builder().backend(backend.clone()).build().unwrap();
+
+    // cpu 0 disappears from the later sub-intervals and from the whole window
+    let disable = {
+        let backend = backend.clone();
+        spawn(move || {
+            sleep(Duration::from_millis(75));
+            backend.disable_core_energy(0);
+        })
+    };
+    let sampled = cpu
+        .read_with_confidence(Duration::from_millis(200), 4)
+        .unwrap();
+    disable.join().unwrap();
+
+    let cpus: Vec<_> = sampled
+        .power
+        .core_readings()
+        .map(|reading| reading.cpu)
+        .collect();
+    assert_eq!(cpus, [1, 2]);
+    for (estimate, expected) in sampled.cores.iter().zip([1.0, 2.0]) {
+        assert!((estimate.power - expected).abs() < 0.1, "{:?}", estimate);
+        assert!(estimate.std_dev < 0.1, "{:?}", estimate);
+    }
+}
+
+#[test]
+fn invalid_subsamples_are_rejected_up_front() {
+    let cpu = CpuInfo::builder()
+        .backend(MockBackend::new(1, 1))
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    assert!(matches!(
+        cpu.read_with_confidence(Duration::from_millis(100), 0),
+        Err(Error::InvalidSubsamples { subsamples: 0, .. })
+    ));
+    // sub-intervals shorter than the counter updates
+    match cpu.read_with_confidence(Duration::from_secs(1), u32::MAX) {
+        Err(Error::InvalidSubsamples { subsamples, max }) => {
+            assert_eq!(subsamples, u32::MAX);
+            assert_eq!(max, 1000);
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    assert!(matches!(
+        cpu.read_with_confidence(Duration::ZERO, 4),
+        Err(Error::EmptyInterval)
+    ));
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // a single sub-interval is still raised to two
+    let sampled = cpu
+        .read_with_confidence(Duration::from_millis(20), 1)
+        .unwrap();
+    assert_eq!(sampled.subsamples, 2);
+}
@@ -0,0 +1,132 @@
+use ryzen_reader::descriptor::{FamilyDescriptor, FrequencyEncoding, VoltageEncoding};
+use ryzen_reader::support::{generation, supported_domains, Generation};
+use ryzen_reader::Error;
+use std::fs::{read_to_string, remove_file, write};
+use std::path::Path;
+
+fn fixture(name: &str) -> FamilyDescriptor {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/descriptors")
+        .join(format!("{}.txt", name));
+    FamilyDescriptor::default()
+        .with_overrides(&read_to_string(path).unwrap())
+        .unwrap()
+}
+
+#[test]
+fn builtin_descriptors_match_fixtures() {
+    let cases = [
+        (0x17, 0x01, Some(Generation::Zen), "zen"),
+        (0x17, 0x08, Some(Generation::Zen), "zen"),
+        (0x17, 0x71, Some(Generation::Zen2), "zen2"),
+        (0x18, 0x00, Some(Generation::Dhyana), "dhyana"),
+        (0x19, 0x21, Some(Generation::Zen3), "zen3"),
+        (0x19, 0x61, Some(Generation::Zen4), "zen4"),
+        (0x19, 0xA0, Some(Generation::Zen4), "zen4"),
+        (0x1A, 0x44, Some(Generation::Zen5), "zen5"),
+        (0x15, 0x02, None, "unknown"),
+        (0x1B, 0x00, None, "unknown_future"),
+    ];
+    for (family, model, expected, name) in cases {
+        assert_eq!(
+            generation(family, model),
+            expected,
+            "{:x}h {:x}h",
+            family,
+            model
+        );
+        assert_eq!(
+            FamilyDescriptor::for_cpu(family, model),
+            fixture(name),
+            "{:x}h {:x}h",
+            family,
+            model
+        );
+    }
+}
+
+#[test]
+fn supported_domains_use_descriptor_addresses() {
+    let domains = supported_domains(0x19, 0x21);
+    let descriptor = FamilyDescriptor::for_cpu(0x19, 0x21);
+    assert_eq!(
+        domains.registers,
+        [
+            descriptor.power_unit,
+            descriptor.core_energy,
+            descriptor.package_energy
+        ]
+    );
+    assert!(supported_domains(0x15, 0x02).registers.is_empty());
+}
+
+#[test]
+fn overrides_replace_listed_fields() {
+    let descriptor = fixture("zen4")
+        .with_overrides(
+            "# moved counters\n\
+             core_energy = 0xC00102A0\n\
+             \n\
+             package_energy = 3221291681\n\
+             voltage = unknown\n",
+        )
+        .unwrap();
+    assert_eq!(descriptor.core_energy, 0xC001_02A0);
+    assert_eq!(descriptor.package_energy, 0xC001_02A1);
+    assert_eq!(descriptor.voltage, VoltageEncoding::Unknown);
+    assert_eq!(descriptor.power_unit, 0xC001_0299);
+    assert_eq!(descriptor.frequency, FrequencyEncoding::FidDivisor);
+}
+
+#[test]
+fn invalid_overrides_report_the_line() {
+    let cases = [
+        "voltage = svi9",
+        "frequency = fid_divisor\ncore_energy = 0xZZ",
+        "# comment\n\nno separator",
+        "unknown_field = 1",
+        "energy_unit_mask = 0",
+        "time_unit_mask = 0x0",
+        "power_unit_mask = 0xF0F",
+        "voltage = svi3\nenergy_unit_mask = 0x1100",
+    ];
+    for overrides in cases {
+        match FamilyDescriptor::default().with_overrides(overrides) {
+            Err(Error::InvalidDescriptor { line, content }) => {
+                let last = overrides.lines().last().unwrap();
+                assert_eq!(line, overrides.lines().count());
+                assert_eq!(content, last);
+            }
+            result => panic!("unexpected result for {:?}: {:?}", overrides, result),
+        }
+    }
+}
+
+#[test]
+fn load_applies_overrides_to_the_current_descriptor() {
+    let path = std::env::temp_dir().join(format!(
+        "ryzen-reader-descriptor-{}.txt",
+        std::process::id()
+    ));
+    write(&path, "power_unit = 0xC0010300\nfrequency = fid_5mhz\n").unwrap();
+    let loaded = FamilyDescriptor::load(&path);
+    remove_file(&path).unwrap();
+
+    let loaded = loaded.unwrap();
+    let current = FamilyDescriptor::current();
+    assert_eq!(loaded.power_unit, 0xC001_0300);
+    assert_eq!(loaded.frequency, FrequencyEncoding::Fid5Mhz);
+    assert_eq!(loaded.core_energy, current.core_energy);
+    assert_eq!(loaded.voltage, current.voltage);
+
+    assert!(FamilyDescriptor::load(path.with_extension("missing")).is_err());
+}
+
+#[test]
+fn full_width_mask_is_accepted() {
+    let descriptor = FamilyDescriptor::default()
+        .with_overrides("power_unit_mask = 0xFFFFFFFFFFFFFFFF\ntime_unit_mask = 0x8000000000000000")
+        .unwrap();
+    assert_eq!(descriptor.power_unit_mask, u64::MAX);
+    assert_eq!(descriptor.time_unit_mask, 1 << 63);
+}
@@ -0,0 +1,41 @@
+use ryzen_reader::load::{LoadGenerator, LoadPattern};
+use ryzen_reader::Error;
+use std::time::Duration;
+
+#[test]
+fn duty_is_clamped() {
+    assert_eq!(LoadPattern::duty(-0.5).duty_cycle(), 0.0);
+    assert_eq!(LoadPattern::duty(2.0).duty_cycle(), 1.0);
+    assert_eq!(LoadPattern::duty(f64::INFINITY).duty_cycle(), 1.0);
+    assert_eq!(LoadPattern::duty(f64::NEG_INFINITY).duty_cycle(), 0.0);
+    assert_eq!(LoadPattern::duty(0.25).duty_cycle(), 0.25);
+}
+
+#[test]
+fn invalid_patterns_are_rejected_before_starting() {
+    // no threads are spawned, so an unpinnable cpu doesn't matter
+    for pattern in [
+        LoadPattern::duty(f64::NAN),
+        LoadPattern::duty(0.5).with_period(Duration::ZERO),
+    ] {
+        assert!(
+            matches!(
+                LoadGenerator::start(&[0, u32::MAX], pattern),
+                Err(Error::InvalidLoadPattern(_))
+            ),
+            "{:?}",
+            pattern
+        );
+    }
+}
+
+#[test]
+fn valid_patterns_run_until_stopped() {
+    let pattern = LoadPattern::duty(0.5).with_period(Duration::from_millis(10));
+    assert_eq!(pattern.period(), Duration::from_millis(10));
+    assert!(!pattern.avx());
+    let load = LoadGenerator::start(&[0], pattern).unwrap();
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(load.started().elapsed() >= Duration::from_millis(30));
+    load.stop();
+}
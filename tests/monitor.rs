@@ -0,0 +1,52 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::{CpuInfo, Error};
+use std::time::Duration;
+
+fn mock_cpu() -> CpuInfo {
+    let backend = MockBackend::new(1, 1);
+    backend.set_power(0, 5.0, 20.0);
+    CpuInfo::builder().backend(backend).build().unwrap()
+}
+
+#[test]
+fn zero_interval_is_rejected() {
+    assert!(matches!(
+        mock_cpu().monitor(Duration::ZERO),
+        Err(Error::EmptyInterval)
+    ));
+}
+
+#[test]
+fn invalid_adaptive_bounds_are_rejected() {
+    let cpu = mock_cpu();
+    let monitor = || cpu.monitor(Duration::from_millis(10)).unwrap();
+
+    assert!(matches!(
+        monitor().adaptive(Duration::ZERO, Duration::from_secs(1)),
+        Err(Error::EmptyInterval)
+    ));
+    assert!(matches!(
+        monitor().adaptive(Duration::from_secs(2), Duration::from_secs(1)),
+        Err(Error::InvalidIntervalBounds { .. })
+    ));
+    assert!(matches!(
+        monitor().adaptive(Duration::from_millis(10), cpu.max_interval() * 2),
+        Err(Error::IntervalTooLong { .. })
+    ));
+}
+
+#[test]
+fn adaptive_interval_stays_within_bounds() {
+    let cpu = mock_cpu();
+    let (min, max) = (Duration::from_millis(5), Duration::from_millis(20));
+    let monitor = cpu
+        .monitor(Duration::from_millis(1))
+        .unwrap()
+        .adaptive(min, max)
+        .unwrap();
+    for power in monitor.take(6) {
+        let power = power.unwrap();
+        assert!(power.quality().interval >= min);
+        assert!((power.packages().sum::<f64>() - 20.0).abs() < 0.5);
+    }
+}
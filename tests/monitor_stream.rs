@@ -0,0 +1,37 @@
+#![cfg(feature = "tokio")]
+
+use futures_core::Stream;
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::CpuInfo;
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn stream_yields_readings() {
+    let backend = MockBackend::new(1, 2);
+    backend.set_power(0, 5.0, 20.0);
+    backend.set_power(1, 5.0, 20.0);
+    let cpu = Arc::new(CpuInfo::builder().backend(backend).build().unwrap());
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let mut stream = cpu
+            .monitor_async(Duration::from_millis(20))
+            .await
+            .unwrap()
+            .into_stream();
+        for _ in 0..3 {
+            let power = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+                .await
+                .expect("the stream never ends")
+                .unwrap();
+            let package: f64 = power.packages().sum();
+            assert!((package - 20.0).abs() < 1.0, "{}", package);
+        }
+    });
+}
@@ -0,0 +1,36 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::clock::ManualClock;
+use ryzen_reader::{CpuInfo, CpuPower};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn cores_ordered_by_package_then_cpu() {
+    let clock = Arc::new(ManualClock::new());
+    // the kernel numbers the cpus of both packages interleaved
+    let backend = MockBackend::from_layout(
+        &[(5, 0), (0, 1), (3, 0), (2, 1), (1, 0), (4, 1)],
+        clock.clone(),
+    );
+    for cpu in 0..6 {
+        backend.set_power(cpu, cpu as f64 + 1.0, 50.0);
+    }
+    let cpu = CpuInfo::builder()
+        .backend(backend)
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+
+    let start = cpu.snapshot().unwrap();
+    clock.advance(Duration::from_secs(1));
+    let power = CpuPower::between(&start, &cpu.snapshot().unwrap()).unwrap();
+
+    let order: Vec<_> = power
+        .core_readings()
+        .map(|reading| (reading.package, reading.cpu))
+        .collect();
+    assert_eq!(order, [(0, 1), (0, 3), (0, 5), (1, 0), (1, 2), (1, 4)]);
+
+    let cores: Vec<_> = power.cores().map(f64::round).collect();
+    assert_eq!(cores, [2.0, 4.0, 6.0, 1.0, 3.0, 5.0]);
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "limits")]
+
+use ryzen_reader::powercap::PackageZone;
+use ryzen_reader::Error;
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+
+/// A fake powercap zone with a single long term constraint of 65W and a maximum of 88W
+fn fake_zone(name: &str) -> (PathBuf, PackageZone) {
+    let path = std::env::temp_dir().join(format!(
+        "ryzen-reader-powercap-{}-{}",
+        name,
+        std::process::id()
+    ));
+    create_dir_all(&path).unwrap();
+    write(path.join("name"), "package-0\n").unwrap();
+    write(path.join("constraint_0_name"), "long_term\n").unwrap();
+    write(path.join("constraint_0_power_limit_uw"), "65000000\n").unwrap();
+    write(path.join("constraint_0_max_power_uw"), "88000000\n").unwrap();
+    let zone = PackageZone::at(&path, 0);
+    (path, zone)
+}
+
+fn limit(path: &Path) -> String {
+    read_to_string(path.join("constraint_0_power_limit_uw")).unwrap()
+}
+
+#[test]
+fn invalid_limits_are_not_written() {
+    let (path, zone) = fake_zone("invalid");
+    for watt in [f64::NAN, f64::INFINITY, -10.0, 0.0] {
+        assert!(
+            matches!(
+                zone.set_power_limit(0, watt),
+                Err(Error::InvalidPowerLimit(_))
+            ),
+            "{}",
+            watt
+        );
+        assert_eq!(limit(&path), "65000000\n");
+    }
+    remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn limits_above_the_maximum_are_not_written() {
+    let (path, zone) = fake_zone("max");
+    match zone.set_power_limit(0, 120.0) {
+        Err(Error::PowerLimitTooHigh { watt, max }) => {
+            assert_eq!(watt, 120.0);
+            assert_eq!(max, 88.0);
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(limit(&path), "65000000\n");
+    remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn valid_limits_are_written_in_microwatt() {
+    let (path, zone) = fake_zone("valid");
+    zone.set_power_limit(0, 45.5).unwrap();
+    assert_eq!(limit(&path), "45500000");
+    let constraints = zone.constraints().unwrap();
+    assert_eq!(constraints[0].power_limit, 45.5);
+    assert_eq!(constraints[0].max_power, Some(88.0));
+    remove_dir_all(path).unwrap();
+}
@@ -0,0 +1,62 @@
+use ryzen_reader::backend::{MockBackend, PowerBackend};
+use ryzen_reader::process::Energy;
+use ryzen_reader::{CpuInfo, Error};
+use std::fs::{read_to_string, remove_file};
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A mock backend that starts failing after a number of package reads
+#[derive(Debug)]
+struct FailingBackend {
+    mock: MockBackend,
+    reads_left: AtomicUsize,
+}
+
+impl PowerBackend for FailingBackend {
+    fn cpus(&self) -> Result<Vec<(u32, u32)>, Error> {
+        self.mock.cpus()
+    }
+
+    fn read_units(&self, cpu: u32) -> io::Result<u64> {
+        self.mock.read_units(cpu)
+    }
+
+    fn read_core_energy(&self, cpu: u32) -> io::Result<u64> {
+        self.mock.read_core_energy(cpu)
+    }
+
+    fn read_package_energy(&self, cpu: u32) -> io::Result<u64> {
+        let left = self.reads_left.load(Ordering::SeqCst);
+        if left == 0 {
+            return Err(io::Error::from_raw_os_error(libc::EPERM));
+        }
+        self.reads_left.store(left - 1, Ordering::SeqCst);
+        self.mock.read_package_energy(cpu)
+    }
+}
+
+#[test]
+fn command_is_reaped_when_measuring_fails() {
+    let backend = Arc::new(FailingBackend {
+        mock: MockBackend::new(1, 1),
+        reads_left: AtomicUsize::new(usize::MAX),
+    });
+    let cpu = CpuInfo::builder().backend(backend.clone()).build().unwrap();
+    // about 300ms of samples before the counters fail
+    backend.reads_left.store(30, Ordering::SeqCst);
+
+    let pid_file = std::env::temp_dir().join(format!("ryzen-reader-child-{}", std::process::id()));
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(format!("echo $$ > {}; exec sleep 30", pid_file.display()));
+    assert!(Energy::of_with(&cpu, command).is_err());
+
+    let pid = read_to_string(&pid_file).unwrap();
+    remove_file(&pid_file).unwrap();
+    // a zombie would keep its /proc entry until it is waited for
+    assert!(!Path::new(&format!("/proc/{}", pid.trim())).exists());
+}
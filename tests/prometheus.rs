@@ -0,0 +1,147 @@
+#![cfg(feature = "prometheus")]
+
+use ryzen_reader::backend::{MockBackend, PowerBackend};
+use ryzen_reader::clock::ManualClock;
+use ryzen_reader::prometheus::{render, Exporter};
+use ryzen_reader::{CpuInfo, CpuPower, Error, SCHEMA_VERSION};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::spawn;
+use std::time::Duration;
+
+/// A mock backend whose package reads can be made to fail
+#[derive(Debug)]
+struct FlakyBackend {
+    mock: MockBackend,
+    failing: AtomicBool,
+}
+
+impl PowerBackend for FlakyBackend {
+    fn cpus(&self) -> Result<Vec<(u32, u32)>, Error> {
+        self.mock.cpus()
+    }
+
+    fn read_units(&self, cpu: u32) -> io::Result<u64> {
+        self.mock.read_units(cpu)
+    }
+
+    fn read_core_energy(&self, cpu: u32) -> io::Result<u64> {
+        self.mock.read_core_energy(cpu)
+    }
+
+    fn read_package_energy(&self, cpu: u32) -> io::Result<u64> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err(io::Error::from_raw_os_error(libc::EPERM));
+        }
+        self.mock.read_package_energy(cpu)
+    }
+}
+
+fn flaky(mock: MockBackend) -> Arc<FlakyBackend> {
+    Arc::new(FlakyBackend {
+        mock,
+        failing: AtomicBool::new(false),
+    })
+}
+
+fn exporter() -> (Arc<ManualClock>, Arc<FlakyBackend>, Exporter) {
+    let clock = Arc::new(ManualClock::new());
+    let backend = flaky(MockBackend::with_clock(1, 2, clock.clone()));
+    let cpu = CpuInfo::builder()
+        .backend(backend.clone())
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    (clock, backend, Exporter::new(cpu))
+}
+
+fn field<'a>(report: &'a str, name: &str) -> &'a str {
+    report
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("no {} in {}", name, report))
+}
+
+#[test]
+fn health_reports_source_age_and_errors() {
+    let (clock, backend, exporter) = exporter();
+    let report = exporter.healthz();
+    assert_eq!(field(&report, "status"), "ok");
+    assert_eq!(field(&report, "source"), "backend");
+    assert_eq!(field(&report, "last_sample_age_seconds"), "-");
+    assert_eq!(field(&report, "errors"), "0");
+
+    exporter.readyz().unwrap();
+    clock.advance(Duration::from_secs(3));
+    let report = exporter.healthz();
+    assert_eq!(field(&report, "last_sample_age_seconds"), "3");
+
+    backend.failing.store(true, Ordering::SeqCst);
+    let report = exporter.readyz().unwrap_err();
+    assert!(field(&report, "status").starts_with("error"), "{}", report);
+    assert_eq!(field(&report, "errors"), "1");
+    // the failed sample doesn't count as a successful one
+    clock.advance(Duration::from_secs(2));
+    let report = exporter.healthz();
+    assert_eq!(field(&report, "status"), "ok");
+    assert_eq!(field(&report, "last_sample_age_seconds"), "5");
+
+    backend.failing.store(false, Ordering::SeqCst);
+    let report = exporter.readyz().unwrap();
+    assert_eq!(field(&report, "last_sample_age_seconds"), "0");
+    assert_eq!(field(&report, "errors"), "1");
+}
+
+fn get(address: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn health_endpoints_are_served() {
+    // the first scrape takes a blocking reading, which needs the real clock
+    let backend = flaky(MockBackend::new(1, 2));
+    let exporter = Exporter::new(CpuInfo::builder().backend(backend.clone()).build().unwrap());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    spawn(move || exporter.serve_listener(listener));
+
+    let ready = get(address, "/readyz");
+    assert!(ready.starts_with("HTTP/1.1 200 OK\r\n"), "{}", ready);
+    assert!(ready.contains("\r\n\r\nstatus ok\n"), "{}", ready);
+
+    let metrics = get(address, "/metrics");
+    assert!(metrics.starts_with("HTTP/1.1 200 OK\r\n"), "{}", metrics);
+    assert!(metrics.contains("ryzen_package_power_watts"), "{}", metrics);
+
+    backend.failing.store(true, Ordering::SeqCst);
+    let ready = get(address, "/readyz");
+    assert!(
+        ready.starts_with("HTTP/1.1 503 Service Unavailable\r\n"),
+        "{}",
+        ready
+    );
+    let health = get(address, "/healthz");
+    assert!(health.starts_with("HTTP/1.1 200 OK\r\n"), "{}", health);
+    assert!(health.contains("errors 1\n"), "{}", health);
+}
+
+#[test]
+fn metrics_report_the_schema_version() {
+    let clock = Arc::new(ManualClock::new());
+    let cpu = CpuInfo::builder()
+        .backend(MockBackend::with_clock(1, 1, clock.clone()))
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    let start = cpu.snapshot().unwrap();
+    clock.advance(Duration::from_secs(1));
+    let metrics = render(&CpuPower::between(&start, &cpu.snapshot().unwrap()).unwrap());
+    let version = format!("ryzen_schema_version {}\n", SCHEMA_VERSION);
+    assert!(metrics.contains(&version), "{}", metrics);
+}
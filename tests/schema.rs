@@ -0,0 +1,108 @@
+#![cfg(feature = "serde")]
+
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::clock::ManualClock;
+use ryzen_reader::{CpuInfo, CpuPower, SCHEMA_VERSION};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A reading in the schema from before the versioning
+const UNVERSIONED: &str = include_str!("fixtures/schema/cpu_power_unversioned.json");
+
+fn mock_cpu() -> (Arc<ManualClock>, CpuInfo) {
+    let clock = Arc::new(ManualClock::new());
+    let backend = MockBackend::with_clock(1, 2, clock.clone());
+    backend.set_power(0, 4.0, 20.0);
+    backend.set_power(1, 6.0, 20.0);
+    let cpu = CpuInfo::builder()
+        .backend(backend)
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    (clock, cpu)
+}
+
+fn reading() -> CpuPower {
+    let (clock, cpu) = mock_cpu();
+    let start = cpu.snapshot().unwrap();
+    clock.advance(Duration::from_secs(1));
+    CpuPower::between(&start, &cpu.snapshot().unwrap()).unwrap()
+}
+
+fn keys(value: &Value) -> Vec<&str> {
+    value
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect()
+}
+
+#[test]
+fn reading_reports_the_schema_version() {
+    let value = serde_json::to_value(reading()).unwrap();
+    assert_eq!(value["schema_version"], SCHEMA_VERSION);
+
+    // fields of version 1 that parsers rely on
+    let keys = keys(&value);
+    for key in ["cores", "package_aggregation", "quality", "stats"] {
+        assert!(keys.contains(&key), "{:?}", keys);
+    }
+    let core = &value["cores"][0];
+    for key in ["core_power", "package_power", "cpu", "package", "physical"] {
+        assert!(core.get(key).is_some(), "{}", core);
+    }
+    assert_eq!(core["core_power"], 4.0);
+    assert_eq!(value["cores"][1]["cpu"], 1);
+}
+
+#[test]
+fn unversioned_readings_are_read_as_version_1() {
+    let power: CpuPower = serde_json::from_str(UNVERSIONED).unwrap();
+    let cores: Vec<_> = power.cores().collect();
+    assert_eq!(cores, [4.0, 6.0]);
+    assert_eq!(power.packages().collect::<Vec<_>>(), [20.0]);
+    assert_eq!(power.sources().count(), 0);
+
+    // and are written back with the version
+    let value = serde_json::to_value(power).unwrap();
+    assert_eq!(value["schema_version"], SCHEMA_VERSION);
+}
+
+#[test]
+fn readings_round_trip() {
+    let json = serde_json::to_string(&reading()).unwrap();
+    let power: CpuPower = serde_json::from_str(&json).unwrap();
+    assert_eq!(power.cores().collect::<Vec<_>>(), [4.0, 6.0]);
+}
+
+#[test]
+fn newer_schema_versions_are_rejected() {
+    let mut value = serde_json::to_value(reading()).unwrap();
+    value["schema_version"] = (SCHEMA_VERSION + 1).into();
+    let error = serde_json::from_value::<CpuPower>(value).unwrap_err();
+    assert!(
+        error.to_string().contains("unsupported schema version"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn accumulated_energy_reports_the_schema_version() {
+    let (clock, cpu) = mock_cpu();
+    clock.advance(Duration::from_secs(2));
+    let value = serde_json::to_value(cpu.energy_since_init().unwrap()).unwrap();
+    assert_eq!(value["schema_version"], SCHEMA_VERSION);
+}
+
+#[test]
+fn sensors_json_reports_the_schema_version() {
+    let json = reading().sensors_json().to_string();
+    let value: Value = serde_json::from_str(&json).unwrap();
+    let chip = &value["ryzen_reader-isa-0000"];
+    assert_eq!(chip["schema_version"], SCHEMA_VERSION);
+    assert_eq!(chip["Adapter"], "ISA adapter");
+    assert_eq!(chip["package0"]["power1_input"], 20.0);
+}
@@ -0,0 +1,41 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::clock::ManualClock;
+use ryzen_reader::{CpuInfo, CpuPower};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn cores_are_labelled_with_the_cpu_id() {
+    let clock = Arc::new(ManualClock::new());
+    let backend = MockBackend::from_layout(&[(0, 0), (1, 0), (2, 0), (3, 0)], clock.clone());
+    for cpu in 0..4 {
+        backend.set_power(cpu, cpu as f64 + 1.0, 20.0);
+    }
+    let cpu = CpuInfo::builder()
+        .backend(backend)
+        .clock(clock.clone())
+        .cpus(&[1, 3])
+        .build()
+        .unwrap();
+    let start = cpu.snapshot().unwrap();
+    clock.advance(Duration::from_secs(1));
+    let power = CpuPower::between(&start, &cpu.snapshot().unwrap()).unwrap();
+
+    let text = power.sensors().to_string();
+    let labels: Vec<_> = text
+        .lines()
+        .skip(2)
+        .map(|line| line.split(':').next().unwrap())
+        .collect();
+    assert_eq!(labels, ["package0", "core1", "core3"]);
+    let core3 = text
+        .lines()
+        .find(|line| line.starts_with("core3:"))
+        .unwrap();
+    assert!(core3.ends_with(" 4.00 W"), "{}", text);
+
+    let json = power.sensors_json().to_string();
+    assert!(json.contains("\"core1\":{"), "{}", json);
+    assert!(json.contains("\"core3\":{"), "{}", json);
+    assert!(!json.contains("\"core0\""), "{}", json);
+}
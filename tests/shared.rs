@@ -0,0 +1,57 @@
+#![cfg(feature = "shared")]
+
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::shared::SharedSampler;
+use ryzen_reader::{CpuInfo, Error};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn sampler() -> Arc<SharedSampler> {
+    let backend = MockBackend::new(1, 2);
+    backend.set_power(0, 5.0, 20.0);
+    backend.set_power(1, 5.0, 20.0);
+    let cpu = CpuInfo::builder().backend(backend).build().unwrap();
+    Arc::new(SharedSampler::new(cpu, Duration::from_millis(5)))
+}
+
+#[test]
+fn zero_interval_is_rejected() {
+    assert!(matches!(
+        sampler().subscribe(Duration::ZERO),
+        Err(Error::EmptyInterval)
+    ));
+}
+
+#[test]
+fn too_long_interval_is_rejected() {
+    let sampler = sampler();
+    let max = sampler.cpu().max_interval();
+    assert!(matches!(
+        sampler.subscribe(max * 2),
+        Err(Error::IntervalTooLong { .. })
+    ));
+}
+
+#[test]
+fn subscribers_average_over_their_own_interval() {
+    let sampler = sampler();
+    let mut fast = sampler.subscribe(Duration::from_millis(20)).unwrap();
+    let mut slow = sampler.subscribe(Duration::from_millis(60)).unwrap();
+    for _ in 0..3 {
+        let power = fast.read().unwrap();
+        assert!((power.packages().sum::<f64>() - 20.0).abs() < 0.5);
+        assert!(power.quality().interval >= Duration::from_millis(20));
+    }
+    let power = slow.read().unwrap();
+    assert!((power.packages().sum::<f64>() - 20.0).abs() < 0.5);
+    assert!(power.quality().interval >= Duration::from_millis(60));
+}
+
+#[test]
+fn fresh_readings_are_shared() {
+    let sampler = sampler();
+    sampler.set_freshness(Duration::from_secs(60));
+    let first = sampler.snapshot().unwrap();
+    let second = sampler.snapshot().unwrap();
+    assert_eq!(first.time(), second.time());
+}
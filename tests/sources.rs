@@ -0,0 +1,87 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::clock::ManualClock;
+use ryzen_reader::{CpuInfo, CpuPower, Error, Source};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn mock(clock: &Arc<ManualClock>, core: f64, package: f64) -> Arc<MockBackend> {
+    let backend = Arc::new(MockBackend::with_clock(1, 2, clock.clone()));
+    backend.set_power(0, core, package);
+    backend.set_power(1, core, package);
+    backend
+}
+
+fn packages(power: &CpuPower) -> Vec<f64> {
+    power.packages().collect()
+}
+
+#[test]
+fn additional_sources_are_read_side_by_side() {
+    let clock = Arc::new(ManualClock::new());
+    let cpu = CpuInfo::builder()
+        .backend(mock(&clock, 5.0, 20.0))
+        .also_read(Source::Backend(mock(&clock, 6.0, 24.0)))
+        .also_read(Source::Backend(mock(&clock, 4.0, 16.0)))
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    assert_eq!(cpu.source(), "backend");
+
+    let start = cpu.snapshot().unwrap();
+    clock.advance(Duration::from_secs(2));
+    let power = CpuPower::between(&start, &cpu.snapshot().unwrap()).unwrap();
+    assert_eq!(packages(&power), [20.0]);
+    let sources: Vec<_> = power
+        .sources()
+        .map(|(name, power)| (name.to_string(), packages(power)))
+        .collect();
+    assert_eq!(
+        sources,
+        [
+            ("backend".to_string(), vec![24.0]),
+            ("backend".to_string(), vec![16.0])
+        ]
+    );
+    // additional sources don't nest
+    for (_, power) in power.sources() {
+        assert_eq!(power.sources().count(), 0);
+    }
+}
+
+#[test]
+fn no_additional_sources_by_default() {
+    let clock = Arc::new(ManualClock::new());
+    let cpu = CpuInfo::builder()
+        .backend(mock(&clock, 5.0, 20.0))
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    let start = cpu.snapshot().unwrap();
+    clock.advance(Duration::from_secs(1));
+    let power = CpuPower::between(&start, &cpu.snapshot().unwrap()).unwrap();
+    assert_eq!(power.sources().count(), 0);
+}
+
+#[test]
+fn unavailable_additional_source_fails_to_open() {
+    let clock = Arc::new(ManualClock::new());
+    let result = CpuInfo::builder()
+        .backend(mock(&clock, 5.0, 20.0))
+        .also_read(Source::Backend(Arc::new(MockBackend::new(1, 0))))
+        .build();
+    assert!(matches!(result, Err(Error::NoCores)));
+}
+
+#[test]
+fn requested_source_replaces_the_backend() {
+    let clock = Arc::new(ManualClock::new());
+    let result = CpuInfo::builder()
+        .backend(mock(&clock, 5.0, 20.0))
+        .source(Source::Sysfs)
+        .build();
+    // either a sysfs driver is used or opening fails, the backend is never silently used instead
+    match result {
+        Ok(cpu) => assert_ne!(cpu.source(), "backend"),
+        Err(e) => assert!(matches!(e, Error::SourceUnavailable), "{:?}", e),
+    }
+}
@@ -0,0 +1,31 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::clock::ManualClock;
+use ryzen_reader::{CpuInfo, CpuPower};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn syscalls(backend: MockBackend, clock: Arc<ManualClock>) -> u32 {
+    let cpu = CpuInfo::builder()
+        .backend(backend)
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    let start = cpu.snapshot().unwrap();
+    clock.advance(Duration::from_secs(1));
+    let power = CpuPower::between(&start, &cpu.snapshot().unwrap()).unwrap();
+    power.stats().syscalls
+}
+
+#[test]
+fn unavailable_domains_are_not_counted_as_syscalls() {
+    let clock = Arc::new(ManualClock::new());
+    let all = syscalls(MockBackend::with_clock(1, 2, clock.clone()), clock);
+
+    let clock = Arc::new(ManualClock::new());
+    let backend = MockBackend::with_clock(1, 2, clock.clone());
+    backend.disable_core_energy(1);
+    let partial = syscalls(backend, clock);
+
+    // one core energy read less in each of the two snapshots
+    assert_eq!(partial, all - 2);
+}
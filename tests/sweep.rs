@@ -0,0 +1,67 @@
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::sweep::SweepConfig;
+use ryzen_reader::{CpuInfo, Error};
+use std::time::Duration;
+
+/// A single mocked cpu, the load generator pins to cpu 0 which exists on every machine
+fn mock_cpu() -> CpuInfo {
+    let backend = MockBackend::new(1, 1);
+    backend.set_power(0, 5.0, 20.0);
+    CpuInfo::builder().backend(backend).build().unwrap()
+}
+
+fn quick() -> SweepConfig {
+    SweepConfig::default()
+        .steps(3)
+        .settle(Duration::ZERO)
+        .measure(Duration::from_millis(20))
+}
+
+#[test]
+fn sweep_records_every_load_level() {
+    let sweep = mock_cpu().sweep(&quick()).unwrap();
+    assert_eq!(sweep.packages(), [0]);
+
+    let curve: Vec<_> = sweep.curve(0).collect();
+    let levels: Vec<_> = curve
+        .iter()
+        .map(|(cores, load, _)| (*cores, *load))
+        .collect();
+    assert_eq!(levels, [(1, 0.0), (1, 0.5), (1, 1.0)]);
+    for (_, _, power) in curve {
+        assert!((power - 20.0).abs() < 1.0, "{}", power);
+    }
+    assert_eq!(sweep.curve(1).count(), 0);
+}
+
+#[test]
+fn sweep_runs_every_core_count() {
+    let sweep = mock_cpu()
+        .sweep(&quick().steps(2).core_counts(&[1, 1]))
+        .unwrap();
+    assert_eq!(sweep.points.len(), 4);
+}
+
+#[test]
+fn invalid_sweeps_are_rejected() {
+    let cpu = mock_cpu();
+    for config in [
+        quick().steps(1),
+        quick().core_counts(&[0]),
+        quick().core_counts(&[2]),
+    ] {
+        assert!(
+            matches!(cpu.sweep(&config), Err(Error::InvalidSweep(_))),
+            "{:?}",
+            config
+        );
+    }
+    assert!(matches!(
+        cpu.sweep(&quick().measure(Duration::ZERO)),
+        Err(Error::EmptyInterval)
+    ));
+    assert!(matches!(
+        cpu.sweep(&quick().measure(cpu.max_interval() * 2)),
+        Err(Error::IntervalTooLong { .. })
+    ));
+}
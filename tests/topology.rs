@@ -0,0 +1,86 @@
+use ryzen_reader::CpuInfo;
+use std::fs::{read_dir, read_to_string, remove_dir_all, write};
+use std::path::Path;
+
+/// Build a cpu with a topology cache, the cores themselves might not open on the test machine
+fn build_with_cache(cache: &Path) {
+    let _ = CpuInfo::builder().topology_cache(cache).build();
+}
+
+fn cache_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "ryzen-reader-topology-{}-{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn cache_contains_placement_and_no_temporary_files() {
+    let dir = cache_dir("placement");
+    let cache = dir.join("topology");
+    build_with_cache(&cache);
+
+    let content = read_to_string(&cache).unwrap();
+    let lines: Vec<_> = content.lines().collect();
+    assert_eq!(lines[0], "ryzen-reader topology 2");
+    assert!(lines.len() > 3, "{}", content);
+    for line in &lines[3..] {
+        let fields: Vec<_> = line.split(' ').collect();
+        assert_eq!(fields.len(), 4, "{}", line);
+        for field in &fields[..3] {
+            field.parse::<u32>().unwrap();
+        }
+        assert!(
+            fields[3] == "-" || fields[3].parse::<u32>().is_ok(),
+            "{}",
+            line
+        );
+    }
+
+    let files: Vec<_> = read_dir(&dir).unwrap().collect();
+    assert_eq!(files.len(), 1);
+    remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn truncated_cache_is_rebuilt() {
+    let dir = cache_dir("truncated");
+    let cache = dir.join("topology");
+    build_with_cache(&cache);
+    let complete = read_to_string(&cache).unwrap();
+
+    write(&cache, &complete[..complete.len() / 2]).unwrap();
+    build_with_cache(&cache);
+    assert_eq!(read_to_string(&cache).unwrap(), complete);
+    remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn old_cache_format_is_rebuilt() {
+    let dir = cache_dir("old");
+    let cache = dir.join("topology");
+    build_with_cache(&cache);
+    let complete = read_to_string(&cache).unwrap();
+
+    // the previous format lacked the version line and the placement columns
+    let old: String = complete
+        .lines()
+        .skip(1)
+        .enumerate()
+        .map(|(i, line)| {
+            if i < 2 {
+                format!("{}\n", line)
+            } else {
+                let fields: Vec<_> = line.split(' ').collect();
+                format!("{} {}\n", fields[0], fields[1])
+            }
+        })
+        .collect();
+    write(&cache, old).unwrap();
+    build_with_cache(&cache);
+    assert_eq!(read_to_string(&cache).unwrap(), complete);
+    remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "shared")]
+
+use ryzen_reader::backend::MockBackend;
+use ryzen_reader::clock::ManualClock;
+use ryzen_reader::shared::SharedSampler;
+use ryzen_reader::tracker::EnergyBudgetTracker;
+use ryzen_reader::{CpuInfo, Error};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A tracker on a 20W package, with a freshness window of 100ms
+fn mock_tracker() -> (Arc<ManualClock>, EnergyBudgetTracker) {
+    let clock = Arc::new(ManualClock::new());
+    let backend = MockBackend::with_clock(1, 2, clock.clone());
+    backend.set_power(0, 5.0, 20.0);
+    backend.set_power(1, 5.0, 20.0);
+    let cpu = CpuInfo::builder()
+        .backend(backend)
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    let sampler = Arc::new(SharedSampler::new(cpu, Duration::from_millis(100)));
+    (clock, EnergyBudgetTracker::new(sampler).unwrap())
+}
+
+#[test]
+fn concurrent_requests_share_the_energy() {
+    let (clock, tracker) = mock_tracker();
+
+    let first = tracker.begin("a").unwrap();
+    clock.advance(Duration::from_secs(1));
+    let second = tracker.begin("b").unwrap();
+    clock.advance(Duration::from_secs(1));
+    let first = tracker.end(first).unwrap();
+    clock.advance(Duration::from_secs(1));
+    let second = tracker.end(second).unwrap();
+
+    // 20J alone, then 20J shared by two requests
+    assert!((first - 30.0).abs() < 0.01, "{}", first);
+    assert!((second - 30.0).abs() < 0.01, "{}", second);
+    assert_eq!(tracker.class("a").requests, 1);
+    assert!((tracker.class("b").average() - 30.0).abs() < 0.01);
+}
+
+#[test]
+fn idle_energy_is_not_attributed() {
+    let (clock, tracker) = mock_tracker();
+    clock.advance(Duration::from_secs(5));
+    let ticket = tracker.begin("a").unwrap();
+    clock.advance(Duration::from_secs(1));
+    let energy = tracker.end(ticket).unwrap();
+    assert!((energy - 20.0).abs() < 0.01, "{}", energy);
+}
+
+#[test]
+fn requests_within_the_freshness_window_share_a_snapshot() {
+    let (clock, tracker) = mock_tracker();
+    let ticket = tracker.begin("a").unwrap();
+    clock.advance(Duration::from_millis(50));
+    // the snapshot from begin is reused, no energy has been seen yet
+    assert_eq!(tracker.end(ticket).unwrap(), 0.0);
+}
+
+#[test]
+fn foreign_tickets_are_rejected() {
+    let (_clock, tracker) = mock_tracker();
+    let (_other_clock, other) = mock_tracker();
+    let ticket = other.begin("a").unwrap();
+    assert!(matches!(tracker.end(ticket), Err(Error::UnknownTicket)));
+    assert!(tracker.classes().is_empty());
+}
@@ -0,0 +1,33 @@
+use ryzen_reader::{CpuInfo, Error, Source};
+use std::fs::read_to_string;
+use std::path::Path;
+
+fn vendor() -> Option<String> {
+    let cpuinfo = read_to_string("/proc/cpuinfo").ok()?;
+    let line = cpuinfo.lines().find(|line| line.starts_with("vendor_id"))?;
+    Some(line.split(':').nth(1)?.trim().to_string())
+}
+
+#[test]
+fn intel_without_rapl_is_unsupported() {
+    // only meaningful on intel cpus without powercap, where opening used to fall back to the AMD msrs
+    let vendor = match vendor() {
+        Some(vendor) if vendor == "GenuineIntel" => vendor,
+        _ => return,
+    };
+    if Path::new("/sys/class/powercap/intel-rapl:0").exists() {
+        return;
+    }
+
+    match CpuInfo::new() {
+        Err(Error::UnsupportedVendor(reported)) => assert_eq!(reported, vendor),
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    // explicitly requesting the msrs doesn't read AMD registers either
+    assert!(matches!(
+        CpuInfo::builder().source(Source::Msr).build(),
+        Err(Error::UnsupportedVendor(_))
+    ));
+    let message = CpuInfo::new().err().unwrap().to_string();
+    assert!(message.contains("GenuineIntel"), "{}", message);
+}